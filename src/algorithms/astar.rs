@@ -64,7 +64,7 @@ pub fn astar<V: Ord + Copy, E: Ord + Copy + Add<Output = E> + Zero>(
                 let real_weight = real_weight + weight;
                 if weights
                     .get(&next)
-                    .map_or(true, |&w| real_weight < w)
+                    .is_none_or(|&w| real_weight < w)
                 {
                     let estimated_weight = real_weight + heuristic(next);
                     weights.insert(next, real_weight);
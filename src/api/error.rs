@@ -0,0 +1,203 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use geo_reader::plan_routes::{
+    geo_validation::ValidationError,
+    index::PlanningError,
+    region::RegionError,
+    spatial_search::SearchError,
+};
+use log::error;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Error común de la API. Cada variante tiene un código estable (`code`) que los
+/// clientes pueden usar sin depender del texto del mensaje.
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+    #[error("Invalid request body: {0}")]
+    InvalidBody(String),
+    #[error("Batch of {size} items exceeds the limit of {max}")]
+    BatchTooLarge { size: usize, max: usize },
+    #[error("Matrix of {cells} cells exceeds the limit of {max}")]
+    MatrixTooLarge { cells: usize, max: usize },
+    #[error("Missing API key")]
+    MissingApiKey,
+    #[error("Invalid API key")]
+    InvalidApiKey,
+    #[error("Rate limit exceeded")]
+    RateLimited,
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+    #[error("Unknown region: {0}")]
+    UnknownRegion(String),
+    #[error("No routes found near origin")]
+    NoRoutesNearOrigin,
+    #[error("No routes found near destination")]
+    NoRoutesNearDestination,
+    #[error("No valid routes found between the specified points")]
+    NoRoutesFound,
+    #[error("Point is not inside any loaded department")]
+    AreaNotFound,
+    #[error("Route planning system not initialized")]
+    PlannerUnavailable,
+    #[error("Database connection error: {0}")]
+    DatabaseUnavailable(String),
+    #[error("Database error: {0}")]
+    Database(#[from] tokio_postgres::Error),
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+/// Cuerpo JSON de todas las respuestas de error.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ErrorResponse {
+    /// Siempre `false`
+    pub success: bool,
+    /// Código estable, por ejemplo `no_routes_near_origin` u `outside_service_area`
+    pub code: String,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::InvalidQuery(_) => "invalid_query",
+            ApiError::InvalidBody(_) => "invalid_body",
+            ApiError::BatchTooLarge { .. } => "batch_too_large",
+            ApiError::MatrixTooLarge { .. } => "matrix_too_large",
+            ApiError::MissingApiKey => "missing_api_key",
+            ApiError::InvalidApiKey => "invalid_api_key",
+            ApiError::RateLimited => "rate_limited",
+            ApiError::Validation(e) => e.reason(),
+            ApiError::UnknownRegion(_) => "unknown_region",
+            ApiError::NoRoutesNearOrigin => "no_routes_near_origin",
+            ApiError::NoRoutesNearDestination => "no_routes_near_destination",
+            ApiError::NoRoutesFound => "no_routes_found",
+            ApiError::AreaNotFound => "area_not_found",
+            ApiError::PlannerUnavailable => "planner_unavailable",
+            ApiError::DatabaseUnavailable(_) => "database_unavailable",
+            ApiError::Database(_) => "database_error",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl From<deadpool_postgres::PoolError> for ApiError {
+    fn from(e: deadpool_postgres::PoolError) -> Self {
+        ApiError::DatabaseUnavailable(e.to_string())
+    }
+}
+
+impl From<SearchError> for ApiError {
+    fn from(e: SearchError) -> Self {
+        match e {
+            SearchError::NoRoutesNearOrigin => ApiError::NoRoutesNearOrigin,
+            SearchError::NoRoutesNearDestination => ApiError::NoRoutesNearDestination,
+            SearchError::NoValidPath | SearchError::MaxTransfersExceeded => ApiError::NoRoutesFound,
+            e @ (SearchError::DistanceError(_) | SearchError::CacheError(_)) => ApiError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<PlanningError> for ApiError {
+    fn from(e: PlanningError) -> Self {
+        match e {
+            PlanningError::ValidationError(e) => e.into(),
+            PlanningError::SearchError(e) => e.into(),
+            PlanningError::NoValidRoutes => ApiError::NoRoutesFound,
+            e @ PlanningError::ConfigError(_) => ApiError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<RegionError> for ApiError {
+    fn from(e: RegionError) -> Self {
+        match e {
+            RegionError::UnknownRegion(id) => ApiError::UnknownRegion(id),
+            e => ApiError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidQuery(_) | ApiError::InvalidBody(_) => StatusCode::BAD_REQUEST,
+            ApiError::BatchTooLarge { .. } | ApiError::MatrixTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::MissingApiKey | ApiError::InvalidApiKey => StatusCode::UNAUTHORIZED,
+            ApiError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Validation(ValidationError::InvalidCoordinates) => StatusCode::BAD_REQUEST,
+            ApiError::Validation(ValidationError::GeometryError(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+            // El punto es válido pero no se puede atender (océano, frontera, fuera del área)
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::UnknownRegion(_)
+            | ApiError::NoRoutesNearOrigin
+            | ApiError::NoRoutesNearDestination
+            | ApiError::NoRoutesFound
+            | ApiError::AreaNotFound => StatusCode::NOT_FOUND,
+            ApiError::PlannerUnavailable | ApiError::DatabaseUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Database(_) | ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        if status.is_server_error() {
+            error!("{} ({})", self, self.code());
+        }
+
+        HttpResponse::build(status).json(ErrorResponse {
+            success: false,
+            code: self.code().to_string(),
+            message: self.to_string(),
+        })
+    }
+}
+
+/// Convierte los errores de deserialización de `web::Query` al modelo común.
+pub fn query_error_handler(
+    err: actix_web::error::QueryPayloadError,
+    _req: &actix_web::HttpRequest,
+) -> actix_web::Error {
+    ApiError::InvalidQuery(err.to_string()).into()
+}
+
+/// Igual para los cuerpos JSON (`web::Json`), incluido el límite de tamaño.
+pub fn json_error_handler(
+    err: actix_web::error::JsonPayloadError,
+    _req: &actix_web::HttpRequest,
+) -> actix_web::Error {
+    ApiError::InvalidBody(err.to_string()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_and_codes() {
+        let cases = [
+            (ApiError::Validation(ValidationError::InvalidCoordinates), StatusCode::BAD_REQUEST, "invalid_coordinates"),
+            (
+                ApiError::Validation(ValidationError::InOcean { distance_km: 3.0 }),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "in_ocean",
+            ),
+            (SearchError::NoRoutesNearOrigin.into(), StatusCode::NOT_FOUND, "no_routes_near_origin"),
+            (
+                PlanningError::SearchError(SearchError::NoValidPath).into(),
+                StatusCode::NOT_FOUND,
+                "no_routes_found",
+            ),
+            (RegionError::UnknownRegion("gt".into()).into(), StatusCode::NOT_FOUND, "unknown_region"),
+            (ApiError::PlannerUnavailable, StatusCode::SERVICE_UNAVAILABLE, "planner_unavailable"),
+        ];
+
+        for (error, status, code) in cases {
+            assert_eq!(error.status_code(), status, "{}", code);
+            assert_eq!(error.code(), code);
+        }
+    }
+}
@@ -1,65 +1,111 @@
 use actix_web::{web, HttpResponse, Responder};
-use crate::db::connect_to_db;
-use crate::plan_routes::{
-    index::{RoutePlanner, PlanningError},
+use deadpool_postgres::Pool;
+use geo_reader::db::PoolStatus;
+use geo_reader::metrics::{self, DB_POOL_CONNECTIONS, DB_QUERY_DURATION};
+use geo_reader::utils::result_cache::{CacheConfig, CacheStats, ResultCache};
+use geo_reader::plan_routes::{
+    index::{PlanningError, RoutePlanner},
+    matrix::{MatrixCell, TravelMatrix},
     _structs::{RoutePlan, TransferType},
-    data_loader::DataLoader,
-    geo_validation::GeoValidator,
-    spatial_search::SpatialSearch,
+    geo_validation::{AdminArea, GeoValidator},
+    region::{DatasetInfo, PlannerRegistry, RegionsConfig},
+    export::{feature_collection, plan_features, route_features},
+    tiles::{vector_tile, TileId},
 };
-use crate::queries::{
+use geo_reader::queries::{
+    _structs::Route,
     find_places::find_places_by_name,
     nearby_route::get_nearby_routes,
     find_by_number::get_routes_by_number,
     find_route::find_route
 };
 use geo_types::Point;
-use log::{info, error, debug};
+use log::{info, debug};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use lazy_static::lazy_static;
-use tokio::sync::Mutex;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use rayon::prelude::*;
+use utoipa::{IntoParams, ToSchema};
 
-use std::path::PathBuf;
-use std::fs::create_dir_all;
-use crate::plan_routes::index::PlanningConfig;
+use super::error::{ApiError, ErrorResponse};
+use lazy_static::lazy_static;
+use tokio::sync::RwLock;
 
 // ==================== Estructuras de Datos ====================
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct PlanRoutesQuery {
+    /// Latitud del origen
     start_lat: f64,
+    /// Longitud del origen
     start_lng: f64,
+    /// Latitud del destino
     end_lat: f64,
+    /// Longitud del destino
     end_lng: f64,
+    /// Región a usar; si se omite, la que contiene el origen
+    region: Option<String>,
+    /// `json` (por defecto) o `geojson`
+    #[param(inline)]
+    format: Option<ResponseFormat>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]  // Agregamos Debug para logging
+#[derive(Serialize, Deserialize, Debug, ToSchema)]  // Agregamos Debug para logging
 pub struct PlanningResponse {
     success: bool,
     message: Option<String>,
+    region: Option<String>,
     routes: Option<Vec<RoutePlanResponse>>,
+    origin_area: Option<AdminArea>,
+    destination_area: Option<AdminArea>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct AdminAreaQuery {
+    lat: f64,
+    lng: f64,
+    /// Región a usar; si se omite, la que contiene el punto
+    region: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct RoutePlanResponse {
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct AdminAreaResponse {
+    success: bool,
+    message: Option<String>,
+    region: Option<String>,
+    department: Option<String>,
+    municipality: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct RoutePlanResponse {
     segments: Vec<RouteSegmentResponse>,
     total_distance: f64,
     transfers_count: i32,
     is_interdepartmental: bool,
+    /// Tiempo estimado en minutos
     estimated_time: i32,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)] 
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct RouteSegmentResponse {
     route_code: String,
+    variant_id: String,
+    direction: Option<String>,
     route_name: String,
+    /// `Directo`, `Cercano` o `Próximo`
     transfer_type: String,
     transfer_point: TransferPointResponse,
     segment_distance: f64,
+    fees: Option<f64>,
+    frequency: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct TransferPointResponse {
     latitude: f64,
     longitude: f64,
@@ -67,24 +113,50 @@ pub struct TransferPointResponse {
     distance: f64,
 }
 
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct RegionResponse {
+    id: String,
+    name: Option<String>,
+    is_default: bool,
+}
+
 // ==================== Planificador Global ====================
 
-lazy_static! {
-    static ref ROUTE_PLANNER: Arc<Mutex<Option<RoutePlanner>>> = Arc::new(Mutex::new(None));
+/// Llave normalizada de las consultas a la base que se guardan en caché.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum DbQueryKey {
+    Places(String),
+    NearbyRoutes((i64, i64), i64),
+    ByNumber(String),
+    BusRoute((i64, i64), (i64, i64)),
 }
 
-// ==================== Funciones de Utilidad ====================
-
-fn is_valid_coordinates(lat: f64, lng: f64) -> bool {
-    // Límites aproximados de El Salvador
-    const MIN_LAT: f64 = 13.0;
-    const MAX_LAT: f64 = 14.5;
-    const MIN_LNG: f64 = -90.2;
-    const MAX_LNG: f64 = -87.5;
+lazy_static! {
+    static ref ROUTE_PLANNERS: Arc<RwLock<Option<PlannerRegistry>>> = Arc::new(RwLock::new(None));
+    static ref DB_CACHE: ResultCache<DbQueryKey, Arc<Value>> =
+        ResultCache::new(CacheConfig::from_env("DB", 1024, 300)).named("db");
+    /// Teselas vectoriales ya codificadas, por región pedida y `z/x/y`
+    static ref TILE_CACHE: ResultCache<(Option<String>, TileId), web::Bytes> =
+        ResultCache::new(CacheConfig::from_env("TILE", 4096, 3600)).named("tiles");
+    /// Hilos de los lotes NDJSON: se bloquean mientras el cliente lee, así que no
+    /// comparten el pool global de rayon con el resto de la planificación
+    static ref BATCH_STREAM_POOL: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .num_threads(batch_stream_threads())
+        .thread_name(|index| format!("batch-stream-{}", index))
+        .build()
+        .expect("failed to build batch stream thread pool");
+    /// Último error al cargar los planificadores, para `/ready`
+    static ref PLANNER_INIT_ERROR: Mutex<Option<String>> = Mutex::new(None);
+    static ref STARTED_AT: (Instant, DateTime<Utc>) = (Instant::now(), Utc::now());
+}
 
-    lat >= MIN_LAT && lat <= MAX_LAT && lng >= MIN_LNG && lng <= MAX_LNG
+/// Fija el inicio del proceso para el `uptime` de `/api/status`.
+pub fn mark_started() {
+    lazy_static::initialize(&STARTED_AT);
 }
 
+// ==================== Funciones de Utilidad ====================
+
 fn estimate_travel_time(plan: &RoutePlan) -> i32 {
     let base_time = (plan.total_distance * 3600.0 / 30.0) as i32;  // tiempo en segundos
     let transfer_time = plan.transfers_count * 5 * 60;  // tiempo en segundos
@@ -100,6 +172,8 @@ fn convert_plan_to_response(plan: RoutePlan) -> RoutePlanResponse {
     let segments = plan.routes.into_iter()
         .map(|segment| RouteSegmentResponse {
             route_code: segment.route.codigo_de.unwrap_or_default(),
+            variant_id: segment.variant_id,
+            direction: segment.route.sentido,
             route_name: segment.route.nombre_de.unwrap_or_default(),
             transfer_type: match segment.transfer_type {
                 TransferType::Direct => "Directo".to_string(),
@@ -114,6 +188,8 @@ fn convert_plan_to_response(plan: RoutePlan) -> RoutePlanResponse {
                 distance: segment.transfer_point.distance_to_route,
             },
             segment_distance: segment.segment_distance,
+            fees: segment.route.fees,
+            frequency: segment.route.frequency,
         })
         .collect();
 
@@ -126,246 +202,1064 @@ fn convert_plan_to_response(plan: RoutePlan) -> RoutePlanResponse {
     }
 }
 
-/// Inicializa el planificador de rutas cargando los datos necesarios
+/// Inicializa los planificadores de todas las regiones configuradas
 pub async fn initialize_planner() -> Result<(), Box<dyn std::error::Error>> {
-    info!("Initializing route planner...");
-    
-    // Configurar directorios
-    let data_dir = PathBuf::from("./data");
-    let cache_dir = PathBuf::from("./cache");
-    
-    // Crear directorio de cache si no existe
-    if !cache_dir.exists() {
-        info!("Creating cache directory...");
-        create_dir_all(&cache_dir)?;
-    }
+    info!("Initializing route planners...");
 
-    // Cargar datos
-    let mut data_loader = DataLoader::new(&data_dir);
-    data_loader.load_all()?;
-    info!("Data loaded successfully");
-    
-    // Inicializar componentes
-    let validator = GeoValidator::new(data_loader.departments());
-    
-    info!("Initializing spatial search with cache...");
-    let search = SpatialSearch::new(
-        data_loader.routes().features.clone(),
-        data_loader.bus_stops().features.iter()
-            .map(|feature| feature.properties.clone())
-            .collect(),
-        Some(cache_dir.clone())
-    );
-    
-    // Configurar el planificador
-    let config = PlanningConfig {
-        max_route_distance: 0.05,     // ~5km en grados
-        max_transfer_distance: 0.01,   // ~1km en grados
-        max_transfers: 10,
-        results_limit: 3,
+    let registry = match load_registry().await {
+        Ok(registry) => registry,
+        Err(e) => {
+            *PLANNER_INIT_ERROR.lock().unwrap() = Some(e.to_string());
+            return Err(e);
+        }
     };
-    
-    info!("Creating route planner...");
-    let planner = RoutePlanner::new(validator, search, Some(config));
-    
-    // Actualizar la instancia global
-    let mut planner_guard = ROUTE_PLANNER.lock().await;
-    *planner_guard = Some(planner);
-    
-    info!("Route planner initialized successfully");
+    info!(
+        "Loaded {} region(s), default region: {}",
+        registry.regions().len(),
+        registry.default_region()
+    );
+
+    // Actualizar la instancia global; los planificadores nuevos traen su caché vacío
+    let mut planners_guard = ROUTE_PLANNERS.write().await;
+    *planners_guard = Some(registry);
+    *PLANNER_INIT_ERROR.lock().unwrap() = None;
+    DB_CACHE.invalidate();
+    TILE_CACHE.invalidate();
+
+    info!("Route planners initialized successfully");
     Ok(())
 }
 
+async fn load_registry() -> Result<PlannerRegistry, Box<dyn std::error::Error>> {
+    let config = RegionsConfig::from_env()?;
+    Ok(PlannerRegistry::build(&config).await?)
+}
+
+/// Obtiene el planificador de la región pedida o de la que contiene el punto.
+async fn resolve_planner(
+    region: Option<&str>,
+    point: Point<f64>,
+) -> Result<(String, Arc<RoutePlanner>), ApiError> {
+    let planners_guard = ROUTE_PLANNERS.read().await;
+    let registry = planners_guard.as_ref().ok_or(ApiError::PlannerUnavailable)?;
+    let region = registry.resolve(region, point)?;
+    Ok((region.config.id.clone(), region.planner.clone()))
+}
+
+impl DbQueryKey {
+    fn name(&self) -> &'static str {
+        match self {
+            DbQueryKey::Places(_) => "places",
+            DbQueryKey::NearbyRoutes(..) => "nearby_routes",
+            DbQueryKey::ByNumber(_) => "by_number",
+            DbQueryKey::BusRoute(..) => "bus_route",
+        }
+    }
+}
+
+/// Devuelve el resultado guardado para `key` o ejecuta la consulta y lo guarda.
+async fn cached_query<T, F, Fut>(key: DbQueryKey, query: F) -> Result<Arc<Value>, ApiError>
+where
+    T: Serialize,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T, ApiError>>,
+{
+    if let Some(value) = DB_CACHE.get(&key) {
+        return Ok(value);
+    }
+
+    let start = Instant::now();
+    let result = query().await;
+    DB_QUERY_DURATION
+        .with_label_values(&[key.name(), if result.is_ok() { "ok" } else { "error" }])
+        .observe(start.elapsed().as_secs_f64());
+
+    let value = serde_json::to_value(result?).map_err(|e| ApiError::Internal(e.to_string()))?;
+    let value = Arc::new(value);
+    DB_CACHE.insert(key, value.clone());
+    Ok(value)
+}
+
+/// Formato de respuesta de los endpoints que devuelven geometrías.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    /// `FeatureCollection` para QGIS, Leaflet, etc.
+    Geojson,
+}
+
+fn geojson_response(features: Vec<Value>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/geo+json")
+        .json(feature_collection(features))
+}
+
+/// Responde una lista de [`Route`] de la base en el formato pedido.
+fn routes_response(routes: &Value, format: Option<ResponseFormat>) -> Result<HttpResponse, ApiError> {
+    match format.unwrap_or_default() {
+        ResponseFormat::Json => Ok(HttpResponse::Ok().json(routes)),
+        ResponseFormat::Geojson => {
+            let routes: Vec<Route> =
+                serde_json::from_value(routes.clone()).map_err(|e| ApiError::Internal(e.to_string()))?;
+            Ok(geojson_response(route_features(&routes)))
+        }
+    }
+}
+
+/// Toma una conexión del pool; sin pool (falta `DATABASE_URL`) responde 503.
+async fn db_client(pool: Option<web::Data<Pool>>) -> Result<deadpool_postgres::Client, ApiError> {
+    let pool = pool.ok_or_else(|| ApiError::DatabaseUnavailable("DATABASE_URL is not set".into()))?;
+    Ok(pool.get().await?)
+}
+
 
 // ==================== Handler Principal ====================
 
-pub async fn plan_routes(query: web::Query<PlanRoutesQuery>) -> impl Responder {
+/// Planifica hasta tres combinaciones de rutas entre dos puntos.
+#[utoipa::path(
+    get,
+    path = "/api/plan_routes",
+    tag = "planner",
+    params(PlanRoutesQuery),
+    responses(
+        (status = 200, description = "Planes encontrados; con `format=geojson` un FeatureCollection con tramos a pie y en bus", body = PlanningResponse),
+        (status = 400, description = "Parámetros o coordenadas inválidos", body = ErrorResponse),
+        (status = 404, description = "Región desconocida o sin rutas entre los puntos", body = ErrorResponse),
+        (status = 422, description = "Punto en el océano, cerca de la frontera o fuera del área de servicio", body = ErrorResponse),
+        (status = 503, description = "Planificador no inicializado", body = ErrorResponse),
+    )
+)]
+pub async fn plan_routes(query: web::Query<PlanRoutesQuery>) -> Result<HttpResponse, ApiError> {
     info!("Planning routes from ({}, {}) to ({}, {})", 
           query.start_lat, query.start_lng, query.end_lat, query.end_lng);
 
-    if !is_valid_coordinates(query.start_lat, query.start_lng) 
-        || !is_valid_coordinates(query.end_lat, query.end_lng) {
-        error!("Invalid coordinates provided");
-        return HttpResponse::BadRequest().json(PlanningResponse {
+    let origin = Point::new(query.start_lng, query.start_lat);
+    let destination = Point::new(query.end_lng, query.end_lat);
+
+    GeoValidator::check_coordinates(origin)?;
+    GeoValidator::check_coordinates(destination)?;
+
+    let (region, planner) = resolve_planner(query.region.as_deref(), origin).await?;
+    let span = tracing::Span::current();
+    span.record("region", region.as_str());
+
+    let plans = plan_between(&planner, origin, destination)?;
+
+    debug!("Found {} possible route plans in region {}", plans.len(), region);
+    span.record("plans", plans.len());
+
+    if query.format == Some(ResponseFormat::Geojson) {
+        return Ok(geojson_response(plan_features(planner.search(), origin, destination, &plans)));
+    }
+    let response_plans: Vec<RoutePlanResponse> = plans.into_iter().map(convert_plan_to_response).collect();
+
+    Ok(HttpResponse::Ok().json(PlanningResponse {
+        success: true,
+        message: None,
+        region: Some(region),
+        routes: Some(response_plans),
+        origin_area: Some(planner.validator().locate(origin)),
+        destination_area: Some(planner.validator().locate(destination)),
+    }))
+}
+
+/// Planifica con un planificador ya resuelto; compartido por `plan_routes` y el lote.
+fn plan_between(planner: &RoutePlanner, origin: Point<f64>, destination: Point<f64>)
+    -> Result<Vec<RoutePlan>, ApiError>
+{
+    let plans = planner.plan_route(origin, destination).map_err(|e| {
+        if let PlanningError::ValidationError(e) = &e {
+            info!("Rejected planning request ({}): {}", e.reason(), e);
+        }
+        ApiError::from(e)
+    })?;
+
+    if plans.is_empty() {
+        return Err(ApiError::NoRoutesFound);
+    }
+    Ok(plans)
+}
+
+// ! BATCH
+/// Máximo de pares por lote si `BATCH_MAX_ITEMS` no está definida.
+const DEFAULT_BATCH_MAX_ITEMS: usize = 1000;
+
+fn batch_max_items() -> usize {
+    std::env::var("BATCH_MAX_ITEMS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_BATCH_MAX_ITEMS)
+}
+
+/// Hilos para lotes NDJSON si `BATCH_STREAM_THREADS` no está definida.
+const DEFAULT_BATCH_STREAM_THREADS: usize = 4;
+
+fn batch_stream_threads() -> usize {
+    std::env::var("BATCH_STREAM_THREADS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|threads| *threads > 0)
+        .unwrap_or(DEFAULT_BATCH_STREAM_THREADS)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct BatchPlanItem {
+    /// Identificador del cliente; se devuelve tal cual en el resultado
+    id: Option<String>,
+    start_lat: f64,
+    start_lng: f64,
+    end_lat: f64,
+    end_lng: f64,
+    /// Región del par; si se omite, la de `options` o la que contiene el origen
+    region: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, ToSchema)]
+#[serde(default)]
+pub struct BatchPlanOptions {
+    /// Región por defecto de los pares
+    region: Option<String>,
+    /// Planes por par, como máximo el `results_limit` de la región
+    max_results: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct BatchPlanRequest {
+    items: Vec<BatchPlanItem>,
+    #[serde(default)]
+    options: BatchPlanOptions,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchFormat {
+    #[default]
+    Json,
+    /// Una línea JSON por par, en el orden en que terminan
+    Ndjson,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct BatchPlanQuery {
+    /// `json` (por defecto) o `ndjson`
+    #[param(inline)]
+    format: Option<BatchFormat>,
+}
+
+/// Resultado de un par del lote: planes o el error con el mismo `code` que `/api/plan_routes`.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct BatchPlanResult {
+    /// Posición del par en `items`
+    index: usize,
+    id: Option<String>,
+    success: bool,
+    region: Option<String>,
+    routes: Option<Vec<RoutePlanResponse>>,
+    code: Option<String>,
+    message: Option<String>,
+}
+
+impl BatchPlanResult {
+    fn failed(index: usize, id: Option<String>, region: Option<String>, e: &ApiError) -> Self {
+        Self {
+            index,
+            id,
             success: false,
-            message: Some("Coordinates must be within El Salvador bounds".into()),
+            region,
             routes: None,
-        });
+            code: Some(e.code().to_string()),
+            message: Some(e.to_string()),
+        }
     }
+}
 
-    let planner_guard = ROUTE_PLANNER.lock().await;
-    let planner = match planner_guard.as_ref() {
-        Some(p) => p,
-        None => {
-            error!("Route planner not initialized");
-            return HttpResponse::InternalServerError().json(PlanningResponse {
-                success: false,
-                message: Some("Route planning system not initialized".into()),
-                routes: None,
-            });
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct BatchPlanResponse {
+    success: bool,
+    succeeded: usize,
+    failed: usize,
+    results: Vec<BatchPlanResult>,
+}
+
+/// Par del lote con su planificador ya resuelto, o el error al resolverlo.
+struct BatchJob {
+    index: usize,
+    item: BatchPlanItem,
+    planner: Result<(String, Arc<RoutePlanner>), ApiError>,
+}
+
+fn run_batch_job(job: BatchJob, max_results: Option<usize>) -> BatchPlanResult {
+    let BatchJob { index, item, planner } = job;
+    let (region, planner) = match planner {
+        Ok(resolved) => resolved,
+        Err(e) => return BatchPlanResult::failed(index, item.id, None, &e),
+    };
+
+    let origin = Point::new(item.start_lng, item.start_lat);
+    let destination = Point::new(item.end_lng, item.end_lat);
+    match plan_between(&planner, origin, destination) {
+        Ok(mut plans) => {
+            if let Some(max_results) = max_results {
+                plans.truncate(max_results);
+            }
+            let routes = plans.into_iter().map(convert_plan_to_response).collect();
+            BatchPlanResult {
+                index,
+                id: item.id,
+                success: true,
+                region: Some(region),
+                routes: Some(routes),
+                code: None,
+                message: None,
+            }
         }
+        Err(e) => BatchPlanResult::failed(index, item.id, Some(region), &e),
+    }
+}
+
+/// Planifica varios pares origen/destino en paralelo sobre los planificadores cargados.
+#[utoipa::path(
+    post,
+    path = "/api/plan_routes/batch",
+    tag = "planner",
+    params(BatchPlanQuery),
+    request_body = BatchPlanRequest,
+    responses(
+        (status = 200, description = "Resultado por par; con `format=ndjson` una línea por par", body = BatchPlanResponse),
+        (status = 400, description = "Cuerpo inválido", body = ErrorResponse),
+        (status = 413, description = "Más pares que `BATCH_MAX_ITEMS`", body = ErrorResponse),
+        (status = 503, description = "Planificador no inicializado", body = ErrorResponse),
+    )
+)]
+pub async fn plan_routes_batch(
+    query: web::Query<BatchPlanQuery>,
+    body: web::Json<BatchPlanRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let BatchPlanRequest { items, options } = body.into_inner();
+    let max_items = batch_max_items();
+    if items.len() > max_items {
+        return Err(ApiError::BatchTooLarge { size: items.len(), max: max_items });
+    }
+    info!("Planning batch of {} pairs", items.len());
+
+    // Las regiones se resuelven con el lock tomado; la planificación corre sin él
+    let jobs: Vec<BatchJob> = {
+        let planners_guard = ROUTE_PLANNERS.read().await;
+        let registry = planners_guard.as_ref().ok_or(ApiError::PlannerUnavailable)?;
+        items
+            .into_iter()
+            .enumerate()
+            .map(|(index, item)| {
+                let origin = Point::new(item.start_lng, item.start_lat);
+                let destination = Point::new(item.end_lng, item.end_lat);
+                let planner = GeoValidator::check_coordinates(origin)
+                    .and_then(|_| GeoValidator::check_coordinates(destination))
+                    .map_err(ApiError::from)
+                    .and_then(|_| {
+                        let requested = item.region.as_deref().or(options.region.as_deref());
+                        let region = registry.resolve(requested, origin)?;
+                        Ok((region.config.id.clone(), region.planner.clone()))
+                    });
+                BatchJob { index, item, planner }
+            })
+            .collect()
     };
+    let max_results = options.max_results;
+    // Cada par se planifica dentro del span de la petición para que sus logs lleven el `request_id`
+    let span = tracing::Span::current();
 
-    let origin = Point::new(query.start_lng, query.start_lat);
-    let destination = Point::new(query.end_lng, query.end_lat);
+    if query.format.unwrap_or_default() == BatchFormat::Ndjson {
+        let (sender, receiver) = tokio::sync::mpsc::channel::<BatchPlanResult>(64);
+        BATCH_STREAM_POOL.spawn(move || {
+            // Si el cliente se desconecta el canal se cierra: no se planifica nada más
+            let _ = jobs.into_par_iter().try_for_each_with(sender, |sender, job| {
+                if sender.is_closed() {
+                    return Err(());
+                }
+                let result = span.in_scope(|| run_batch_job(job, max_results));
+                sender.blocking_send(result).map_err(|_| ())
+            });
+        });
 
-    match planner.plan_route(origin, destination) {
-        Ok(plans) => {
-            let response_plans: Vec<RoutePlanResponse> = plans.into_iter()
-                .map(convert_plan_to_response)
-                .collect();
+        let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+            let result = receiver.recv().await?;
+            let mut line = serde_json::to_vec(&result).unwrap_or_default();
+            line.push(b'\n');
+            Some((Ok::<_, actix_web::Error>(web::Bytes::from(line)), receiver))
+        });
+        return Ok(HttpResponse::Ok()
+            .content_type("application/x-ndjson")
+            .streaming(stream));
+    }
 
-            debug!("Found {} possible route plans", response_plans.len());
+    let results: Vec<BatchPlanResult> = web::block(move || {
+        jobs.into_par_iter()
+            .map(|job| span.in_scope(|| run_batch_job(job, max_results)))
+            .collect()
+    })
+    .await
+    .map_err(|e| ApiError::Internal(e.to_string()))?;
 
-            if response_plans.is_empty() {
-                HttpResponse::NotFound().json(PlanningResponse {
-                    success: false,
-                    message: Some("No valid routes found between the specified points".into()),
-                    routes: None,
-                })
-            } else {
-                HttpResponse::Ok().json(PlanningResponse {
-                    success: true,
-                    message: None,
-                    routes: Some(response_plans),
-                })
-            }
-        }
-        Err(e) => {
-            error!("Error planning route: {:?}", e);
-            let error_message = e.to_string();
+    let succeeded = results.iter().filter(|result| result.success).count();
+    Ok(HttpResponse::Ok().json(BatchPlanResponse {
+        success: true,
+        succeeded,
+        failed: results.len() - succeeded,
+        results,
+    }))
+}
+
+// ! MATRIX
+/// Celdas (orígenes × destinos) por matriz si `MATRIX_MAX_CELLS` no está definida.
+const DEFAULT_MATRIX_MAX_CELLS: usize = 10_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, ToSchema)]
+pub struct LatLng {
+    lat: f64,
+    lng: f64,
+}
+
+impl LatLng {
+    fn point(self) -> Point<f64> {
+        Point::new(self.lng, self.lat)
+    }
+}
 
-            HttpResponse::InternalServerError().json(PlanningResponse {
-                success: false,
-                message: Some(error_message),
-                routes: None,
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct MatrixRequest {
+    origins: Vec<LatLng>,
+    destinations: Vec<LatLng>,
+    /// Región a usar; si se omite, la que contiene el primer origen
+    region: Option<String>,
+}
+
+/// Matriz compacta: `minutes[i][j]` es el tiempo del origen `i` al destino `j`,
+/// `null` si no hay plan.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct MatrixResponse {
+    success: bool,
+    region: String,
+    minutes: Vec<Vec<Option<f64>>>,
+    transfers: Vec<Vec<Option<i32>>>,
+    walking_meters: Vec<Vec<Option<u32>>>,
+}
+
+/// Tiempo, transbordos y caminata del mejor plan entre cada origen y cada destino.
+#[utoipa::path(
+    post,
+    path = "/api/matrix",
+    tag = "planner",
+    request_body = MatrixRequest,
+    responses(
+        (status = 200, description = "Matriz de tiempos", body = MatrixResponse),
+        (status = 400, description = "Cuerpo o coordenadas inválidos", body = ErrorResponse),
+        (status = 404, description = "Región desconocida", body = ErrorResponse),
+        (status = 413, description = "Más celdas que `MATRIX_MAX_CELLS`", body = ErrorResponse),
+        (status = 503, description = "Planificador no inicializado", body = ErrorResponse),
+    )
+)]
+pub async fn travel_matrix(body: web::Json<MatrixRequest>) -> Result<HttpResponse, ApiError> {
+    let MatrixRequest { origins, destinations, region } = body.into_inner();
+    let max_cells = std::env::var("MATRIX_MAX_CELLS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MATRIX_MAX_CELLS);
+    let cells = origins.len() * destinations.len();
+    if cells > max_cells {
+        return Err(ApiError::MatrixTooLarge { cells, max: max_cells });
+    }
+
+    let origins: Vec<Point<f64>> = origins.into_iter().map(LatLng::point).collect();
+    let destinations: Vec<Point<f64>> = destinations.into_iter().map(LatLng::point).collect();
+    for point in origins.iter().chain(&destinations) {
+        GeoValidator::check_coordinates(*point)?;
+    }
+    let Some(first) = origins.first().copied() else {
+        return Err(ApiError::InvalidBody("origins must not be empty".to_string()));
+    };
+
+    let (region, planner) = resolve_planner(region.as_deref(), first).await?;
+    info!("Computing {}x{} travel matrix in region {}", origins.len(), destinations.len(), region);
+
+    let span = tracing::Span::current();
+    let matrix = web::block(move || span.in_scope(|| planner.travel_matrix(&origins, &destinations)))
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?;
+
+    fn grid<T>(matrix: &TravelMatrix, f: impl Fn(&MatrixCell) -> T) -> Vec<Vec<Option<T>>> {
+        matrix.cells.iter()
+            .map(|row| row.iter().map(|cell| cell.as_ref().map(&f)).collect())
+            .collect()
+    }
+
+    Ok(HttpResponse::Ok().json(MatrixResponse {
+        success: true,
+        region,
+        minutes: grid(&matrix, |cell| (cell.minutes * 10.0).round() / 10.0),
+        transfers: grid(&matrix, |cell| cell.transfers),
+        walking_meters: grid(&matrix, |cell| cell.walking_meters.round() as u32),
+    }))
+}
+
+// ! TILES
+/// Tipo MIME de las teselas vectoriales.
+const MVT_CONTENT_TYPE: &str = "application/vnd.mapbox-vector-tile";
+
+#[derive(Deserialize, IntoParams)]
+pub struct TileQuery {
+    /// Región a dibujar; si se omite, todas
+    region: Option<String>,
+}
+
+/// Tesela vectorial (MVT) con las capas `routes` y `stops` de los datos en memoria.
+#[utoipa::path(
+    get,
+    path = "/tiles/{z}/{x}/{y}.mvt",
+    tag = "planner",
+    params(
+        ("z" = u8, Path, description = "Zoom, de 0 a 22"),
+        ("x" = u32, Path, description = "Columna de la tesela"),
+        ("y" = u32, Path, description = "Fila de la tesela"),
+        TileQuery,
+    ),
+    responses(
+        (status = 200, description = "Tesela codificada", content_type = "application/vnd.mapbox-vector-tile"),
+        (status = 204, description = "Sin rutas ni paradas en la tesela"),
+        (status = 400, description = "Coordenadas de tesela inválidas", body = ErrorResponse),
+        (status = 404, description = "Región desconocida", body = ErrorResponse),
+        (status = 503, description = "Planificador no inicializado", body = ErrorResponse),
+    )
+)]
+pub async fn vector_tiles(
+    path: web::Path<(u8, u32, u32)>,
+    query: web::Query<TileQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let (z, x, y) = path.into_inner();
+    let tile = TileId::new(z, x, y)
+        .ok_or_else(|| ApiError::InvalidQuery(format!("tile {}/{}/{} is out of range", z, x, y)))?;
+    let region = query.into_inner().region;
+    let key = (region.clone(), tile);
+
+    let body = match TILE_CACHE.get(&key) {
+        Some(body) => body,
+        None => {
+            let planners: Vec<Arc<RoutePlanner>> = {
+                let planners_guard = ROUTE_PLANNERS.read().await;
+                let registry = planners_guard.as_ref().ok_or(ApiError::PlannerUnavailable)?;
+                match region.as_deref() {
+                    Some(id) => {
+                        let region = registry.get(id).ok_or_else(|| ApiError::UnknownRegion(id.to_string()))?;
+                        vec![region.planner.clone()]
+                    }
+                    None => registry.regions().iter().map(|region| region.planner.clone()).collect(),
+                }
+            };
+
+            let span = tracing::Span::current();
+            let body = web::block(move || {
+                span.in_scope(|| web::Bytes::from(vector_tile(planners.iter().map(|planner| planner.search()), tile)))
             })
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+            TILE_CACHE.insert(key, body.clone());
+            body
         }
+    };
+
+    if body.is_empty() {
+        return Ok(HttpResponse::NoContent().finish());
     }
+    Ok(HttpResponse::Ok()
+        .content_type(MVT_CONTENT_TYPE)
+        .insert_header(("Cache-Control", format!("public, max-age={}", TILE_CACHE.config().ttl.as_secs())))
+        .body(body))
+}
+
+// ! ADMIN AREA
+/// Departamento y municipio que contienen un punto.
+#[utoipa::path(
+    get,
+    path = "/api/admin_area",
+    tag = "planner",
+    params(AdminAreaQuery),
+    responses(
+        (status = 200, description = "Área encontrada", body = AdminAreaResponse),
+        (status = 400, description = "Parámetros inválidos", body = ErrorResponse),
+        (status = 404, description = "Región desconocida o punto fuera de los departamentos", body = ErrorResponse),
+        (status = 503, description = "Planificador no inicializado", body = ErrorResponse),
+    )
+)]
+pub async fn admin_area(query: web::Query<AdminAreaQuery>) -> Result<HttpResponse, ApiError> {
+    let point = Point::new(query.lng, query.lat);
+    GeoValidator::check_coordinates(point)?;
+    let (region, planner) = resolve_planner(query.region.as_deref(), point).await?;
+
+    let area = planner.validator().locate(point);
+    if area.department.is_none() {
+        return Err(ApiError::AreaNotFound);
+    }
+
+    Ok(HttpResponse::Ok().json(AdminAreaResponse {
+        success: true,
+        message: None,
+        region: Some(region),
+        department: area.department,
+        municipality: area.municipality,
+    }))
+}
+
+// ! REGIONS
+/// Regiones cargadas en el servidor.
+#[utoipa::path(
+    get,
+    path = "/api/regions",
+    tag = "planner",
+    responses((status = 200, description = "Regiones configuradas", body = [RegionResponse]))
+)]
+pub async fn list_regions() -> impl Responder {
+    let planners_guard = ROUTE_PLANNERS.read().await;
+    let regions: Vec<RegionResponse> = planners_guard
+        .as_ref()
+        .map(|registry| {
+            registry.regions()
+                .iter()
+                .map(|region| RegionResponse {
+                    id: region.config.id.clone(),
+                    name: region.config.name.clone(),
+                    is_default: region.config.id == registry.default_region(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    HttpResponse::Ok().json(regions)
 }
 
 // ! Routes
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct PlaceQuery {
+    /// Texto a buscar en el nombre del lugar
     name: String,
 }
 
 // ! FIND PLACES
-pub async fn find_places(query: web::Query<PlaceQuery>) -> impl Responder {
-    let db_client = match connect_to_db().await {
-        Ok(client) => client,
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .body(format!("Database connection error: {}", e))
-        }
-    };
-
-    match find_places_by_name(&query.name, &db_client).await {
-        Ok(places) => HttpResponse::Ok().json(places),
-        Err(e) => {
-            error!("Error finding places: {}", e);
-            HttpResponse::InternalServerError().body(format!("Error finding places: {}", e))
-        }
-    }
+/// Lugares cuyo nombre contiene el texto, como `[nombre, longitud, latitud]`.
+#[utoipa::path(
+    get,
+    path = "/api/places",
+    tag = "database",
+    params(PlaceQuery),
+    responses(
+        (status = 200, description = "Lugares encontrados", body = [(String, f64, f64)]),
+        (status = 400, description = "Parámetros inválidos", body = ErrorResponse),
+        (status = 500, description = "Error en la consulta", body = ErrorResponse),
+        (status = 503, description = "Base de datos no disponible", body = ErrorResponse),
+    )
+)]
+pub async fn find_places(
+    query: web::Query<PlaceQuery>,
+    pool: Option<web::Data<Pool>>,
+) -> Result<HttpResponse, ApiError> {
+    let key = DbQueryKey::Places(query.name.trim().to_lowercase());
+    let places = cached_query(key, || async {
+        let db_client = db_client(pool).await?;
+        Ok(find_places_by_name(&query.name, &db_client).await?)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(&*places))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct NearbyRoutesQuery {
     latitude: f64,
     longitude: f64,
+    /// Distancia máxima en metros
     max_distance: f64,
+    /// `json` (por defecto) o `geojson`
+    #[param(inline)]
+    format: Option<ResponseFormat>,
 }
 
 // ! GET NEARBY ROUTES
-pub async fn get_nearby_routes_endpoint(query: web::Query<NearbyRoutesQuery>) -> impl Responder {
-    info!("Finding nearby routes...");
-    let db_client = match connect_to_db().await {
-        Ok(client) => client,
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .body(format!("Database connection error: {}", e))
-        }
-    };
-
-    info!("Connected to database...");
-
-    match get_nearby_routes(
-        query.latitude,
-        query.longitude,
-        query.max_distance,
-        &db_client,
+/// Rutas de la base a menos de `max_distance` metros del punto.
+#[utoipa::path(
+    get,
+    path = "/api/nearby_routes",
+    tag = "database",
+    params(NearbyRoutesQuery),
+    responses(
+        (status = 200, description = "Rutas cercanas; con `format=geojson` un FeatureCollection", body = [Route]),
+        (status = 400, description = "Parámetros inválidos", body = ErrorResponse),
+        (status = 500, description = "Error en la consulta", body = ErrorResponse),
+        (status = 503, description = "Base de datos no disponible", body = ErrorResponse),
     )
-    .await
-    {
-        Ok(routes) => HttpResponse::Ok().json(routes),
-        Err(e) => {
-            error!("Error finding nearby routes: {}", e);
-            HttpResponse::InternalServerError().body(format!("Error finding nearby routes: {}", e))
-        }
-    }
+)]
+pub async fn get_nearby_routes_endpoint(
+    query: web::Query<NearbyRoutesQuery>,
+    pool: Option<web::Data<Pool>>,
+) -> Result<HttpResponse, ApiError> {
+    info!("Finding nearby routes...");
+    let point = Point::new(query.longitude, query.latitude);
+    let key = DbQueryKey::NearbyRoutes(DB_CACHE.config().snap(point), query.max_distance.round() as i64);
+    let routes = cached_query(key, || async {
+        let db_client = db_client(pool).await?;
+        Ok(get_nearby_routes(
+            query.latitude,
+            query.longitude,
+            query.max_distance,
+            &db_client,
+        )
+        .await?)
+    })
+    .await?;
+    routes_response(&routes, query.format)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct RouteByNumberQuery {
+    /// Número de la ruta, por ejemplo `101`
     number_route: String,
+    /// `json` (por defecto) o `geojson`
+    #[param(inline)]
+    format: Option<ResponseFormat>,
 }
 
 // ! GET ROUTES BY NUMBER
+/// Rutas de la base con el número indicado.
+#[utoipa::path(
+    get,
+    path = "/api/by_number",
+    tag = "database",
+    params(RouteByNumberQuery),
+    responses(
+        (status = 200, description = "Rutas con ese número; con `format=geojson` un FeatureCollection", body = [Route]),
+        (status = 400, description = "Parámetros inválidos", body = ErrorResponse),
+        (status = 500, description = "Error en la consulta", body = ErrorResponse),
+        (status = 503, description = "Base de datos no disponible", body = ErrorResponse),
+    )
+)]
 pub async fn get_routes_by_number_endpoint(
     query: web::Query<RouteByNumberQuery>,
-) -> impl Responder {
-    let db_client = match connect_to_db().await {
-        Ok(client) => client,
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .body(format!("Database connection error: {}", e))
-        }
-    };
-
-    match get_routes_by_number(query.number_route.clone(), &db_client).await {
-        Ok(routes) => HttpResponse::Ok().json(routes),
-        Err(e) => {
-            error!("Error fetching routes by number: {}", e);
-            HttpResponse::InternalServerError()
-                .body(format!("Error fetching routes by number: {}", e))
-        }
-    }
+    pool: Option<web::Data<Pool>>,
+) -> Result<HttpResponse, ApiError> {
+    let key = DbQueryKey::ByNumber(query.number_route.trim().to_string());
+    let routes = cached_query(key, || async {
+        let db_client = db_client(pool).await?;
+        Ok(get_routes_by_number(query.number_route.clone(), &db_client).await?)
+    })
+    .await?;
+    routes_response(&routes, query.format)
 }
 
 // ! FIND BUS ROUTE
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct RouteQuery {
     start_lat: f64,
     start_lng: f64,
     end_lat: f64,
     end_lng: f64,
+    /// `json` (por defecto) o `geojson`
+    #[param(inline)]
+    format: Option<ResponseFormat>,
 }
 
-pub async fn find_bus_route(query: web::Query<RouteQuery>) -> impl Responder {
-    let db_client = match connect_to_db().await {
-        Ok(client) => client,
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .body(format!("Database connection error: {}", e))
+/// Rutas de la base que pasan cerca de ambos puntos.
+#[utoipa::path(
+    get,
+    path = "/api/bus_route",
+    tag = "database",
+    params(RouteQuery),
+    responses(
+        (status = 200, description = "Rutas que conectan los puntos; con `format=geojson` un FeatureCollection", body = [Route]),
+        (status = 400, description = "Parámetros inválidos", body = ErrorResponse),
+        (status = 500, description = "Error en la consulta", body = ErrorResponse),
+        (status = 503, description = "Base de datos no disponible", body = ErrorResponse),
+    )
+)]
+pub async fn find_bus_route(
+    query: web::Query<RouteQuery>,
+    pool: Option<web::Data<Pool>>,
+) -> Result<HttpResponse, ApiError> {
+    let grid = DB_CACHE.config();
+    let key = DbQueryKey::BusRoute(
+        grid.snap(Point::new(query.start_lng, query.start_lat)),
+        grid.snap(Point::new(query.end_lng, query.end_lat)),
+    );
+    let routes = cached_query(key, || async {
+        let db_client = db_client(pool).await?;
+        Ok(find_route(
+            query.start_lat,
+            query.start_lng,
+            query.end_lat,
+            query.end_lng,
+            &db_client,
+        )
+        .await?)
+    })
+    .await?;
+    routes_response(&routes, query.format)
+}
+
+
+// ! DB POOL
+/// Estado del pool de conexiones a PostgreSQL.
+#[utoipa::path(
+    get,
+    path = "/api/db/pool",
+    tag = "database",
+    responses(
+        (status = 200, description = "Conexiones del pool", body = PoolStatus),
+        (status = 503, description = "Base de datos no configurada", body = ErrorResponse),
+    )
+)]
+pub async fn db_pool_status(pool: Option<web::Data<Pool>>) -> Result<HttpResponse, ApiError> {
+    let pool = pool.ok_or_else(|| ApiError::DatabaseUnavailable("DATABASE_URL is not set".into()))?;
+    Ok(HttpResponse::Ok().json(PoolStatus::of(&pool)))
+}
+
+
+// ! CACHE
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CacheStatsResponse {
+    /// Caché de planes por región
+    plans: BTreeMap<String, CacheStats>,
+    /// Caché de consultas a la base
+    db: CacheStats,
+    /// Caché de teselas vectoriales
+    tiles: CacheStats,
+}
+
+/// Aciertos y fallos de los cachés de resultados.
+#[utoipa::path(
+    get,
+    path = "/api/cache",
+    tag = "planner",
+    responses((status = 200, description = "Contadores de los cachés", body = CacheStatsResponse))
+)]
+pub async fn cache_stats() -> impl Responder {
+    let planners_guard = ROUTE_PLANNERS.read().await;
+    let plans = planners_guard
+        .as_ref()
+        .map(|registry| {
+            registry.regions()
+                .iter()
+                .map(|region| (region.config.id.clone(), region.planner.cache_stats()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    HttpResponse::Ok().json(CacheStatsResponse {
+        plans,
+        db: DB_CACHE.stats(),
+        tiles: TILE_CACHE.stats(),
+    })
+}
+
+
+// ! HEALTH
+/// Tiempo máximo para comprobar la base en `/ready`.
+const READY_DB_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct HealthResponse {
+    status: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ReadinessCheck {
+    ok: bool,
+    detail: Option<String>,
+}
+
+impl ReadinessCheck {
+    fn ok(detail: Option<String>) -> Self {
+        Self { ok: true, detail }
+    }
+
+    fn failed(detail: impl Into<String>) -> Self {
+        Self { ok: false, detail: Some(detail.into()) }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ReadinessResponse {
+    ready: bool,
+    /// `planner`, `database` e `intersections_cache`
+    checks: BTreeMap<String, ReadinessCheck>,
+}
+
+/// El proceso está vivo; no revisa dependencias.
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "status",
+    responses((status = 200, description = "Proceso vivo", body = HealthResponse))
+)]
+pub async fn health() -> impl Responder {
+    HttpResponse::Ok().json(HealthResponse { status: "ok".to_string() })
+}
+
+/// Listo para recibir tráfico: planificador cargado, base alcanzable (si está
+/// configurada) y grafo de transbordos en cache o snapshot.
+#[utoipa::path(
+    get,
+    path = "/ready",
+    tag = "status",
+    responses(
+        (status = 200, description = "Listo", body = ReadinessResponse),
+        (status = 503, description = "Alguna comprobación falló", body = ReadinessResponse),
+    )
+)]
+pub async fn ready(pool: Option<web::Data<Pool>>) -> impl Responder {
+    let mut checks = BTreeMap::new();
+
+    let planners_guard = ROUTE_PLANNERS.read().await;
+    let (planner, intersections) = match planners_guard.as_ref() {
+        Some(registry) => {
+            let missing: Vec<&str> = registry.regions()
+                .iter()
+                .filter(|region| {
+                    !region.planner.search().intersections_cache_path().exists()
+                        && !region.config.snapshot_path().exists()
+                })
+                .map(|region| region.config.id.as_str())
+                .collect();
+            let intersections = if missing.is_empty() {
+                ReadinessCheck::ok(None)
+            } else {
+                ReadinessCheck::failed(format!("No intersection cache for region(s): {}", missing.join(", ")))
+            };
+            (ReadinessCheck::ok(Some(format!("{} region(s) loaded", registry.regions().len()))), intersections)
+        }
+        None => {
+            let detail = PLANNER_INIT_ERROR.lock().unwrap().clone()
+                .unwrap_or_else(|| "Route planning system not initialized".to_string());
+            (ReadinessCheck::failed(detail), ReadinessCheck::failed("Route planning system not initialized"))
         }
     };
+    drop(planners_guard);
+    checks.insert("planner".to_string(), planner);
+    checks.insert("intersections_cache".to_string(), intersections);
 
-    match find_route(
-        query.start_lat,
-        query.start_lng,
-        query.end_lat,
-        query.end_lng,
-        &db_client,
-    )
-    .await
-    {
-        Ok(routes) => HttpResponse::Ok().json(routes),
-        Err(e) => {
-            error!("Error finding route: {}", e);
-            HttpResponse::InternalServerError().body(format!("Error finding route: {}", e))
+    let database = match pool {
+        None => ReadinessCheck::ok(Some("DATABASE_URL is not set".to_string())),
+        Some(pool) => match tokio::time::timeout(READY_DB_TIMEOUT, ping_db(&pool)).await {
+            Ok(Ok(())) => ReadinessCheck::ok(None),
+            Ok(Err(e)) => ReadinessCheck::failed(e.to_string()),
+            Err(_) => ReadinessCheck::failed("Database did not answer in time"),
+        },
+    };
+    checks.insert("database".to_string(), database);
+
+    let ready = checks.values().all(|check| check.ok);
+    let mut response = if ready { HttpResponse::Ok() } else { HttpResponse::ServiceUnavailable() };
+    response.json(ReadinessResponse { ready, checks })
+}
+
+async fn ping_db(pool: &Pool) -> Result<(), ApiError> {
+    let client = pool.get().await?;
+    client.simple_query("SELECT 1").await?;
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct RegionStatus {
+    id: String,
+    dataset: DatasetInfo,
+    routes: usize,
+    stops: usize,
+    departments: usize,
+    municipalities: usize,
+    /// Cuándo se calculó el grafo de transbordos
+    #[schema(value_type = String, format = DateTime)]
+    cache_built_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct StatusResponse {
+    /// Versión del servidor
+    version: String,
+    #[schema(value_type = String, format = DateTime)]
+    started_at: DateTime<Utc>,
+    uptime_secs: u64,
+    default_region: Option<String>,
+    regions: Vec<RegionStatus>,
+}
+
+/// Versión de los datos, conteos y tiempo en línea.
+#[utoipa::path(
+    get,
+    path = "/api/status",
+    tag = "status",
+    responses((status = 200, description = "Estado del servidor", body = StatusResponse))
+)]
+pub async fn status() -> impl Responder {
+    let planners_guard = ROUTE_PLANNERS.read().await;
+    let regions = planners_guard
+        .as_ref()
+        .map(|registry| {
+            registry.regions()
+                .iter()
+                .map(|region| {
+                    let validator = region.planner.validator();
+                    let search = region.planner.search();
+                    RegionStatus {
+                        id: region.config.id.clone(),
+                        dataset: region.dataset.clone(),
+                        routes: search.route_count(),
+                        stops: search.stop_count(),
+                        departments: validator.departments_count(),
+                        municipalities: validator.municipalities_count(),
+                        cache_built_at: search.intersections_built_at(),
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let (started, started_at) = *STARTED_AT;
+    HttpResponse::Ok().json(StatusResponse {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        started_at,
+        uptime_secs: started.elapsed().as_secs(),
+        default_region: planners_guard.as_ref().map(|registry| registry.default_region().to_string()),
+        regions,
+    })
+}
+
+
+// ! METRICS
+/// Métricas en formato de texto de Prometheus.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "status",
+    responses((status = 200, description = "Métricas de Prometheus", content_type = "text/plain"))
+)]
+pub async fn prometheus_metrics(pool: Option<web::Data<Pool>>) -> impl Responder {
+    // Los tamaños de cachés y del pool se leen al momento de exportar
+    if let Some(registry) = ROUTE_PLANNERS.read().await.as_ref() {
+        for region in registry.regions() {
+            metrics::set_cache_stats(&format!("plans:{}", region.config.id), &region.planner.cache_stats());
         }
     }
+    metrics::set_cache_stats("db", &DB_CACHE.stats());
+    metrics::set_cache_stats("tiles", &TILE_CACHE.stats());
+
+    if let Some(pool) = pool {
+        let status = PoolStatus::of(&pool);
+        DB_POOL_CONNECTIONS.with_label_values(&["max"]).set(status.max_size as i64);
+        DB_POOL_CONNECTIONS.with_label_values(&["open"]).set(status.size as i64);
+        DB_POOL_CONNECTIONS.with_label_values(&["available"]).set(status.available as i64);
+        DB_POOL_CONNECTIONS.with_label_values(&["waiting"]).set(status.waiting as i64);
+    }
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics::render())
 }
 
 
@@ -383,16 +1277,15 @@ mod tests {
         ).await;
 
         let req = test::TestRequest::get()
-            .uri(&format!("/?start_lat=0&start_lng=0&end_lat=0&end_lng=0"))
+            .uri("/?start_lat=95&start_lng=0&end_lat=0&end_lng=0")
             .to_request();
         
         let resp = test::call_service(&app, req).await;
         assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 
-        let body: PlanningResponse = test::read_body_json(resp).await;
+        let body: ErrorResponse = test::read_body_json(resp).await;
         assert!(!body.success);
-        assert!(body.message.is_some());
-        assert!(body.routes.is_none());
+        assert_eq!(body.code, "invalid_coordinates");
     }
 
     #[actix_web::test]
@@ -402,24 +1295,239 @@ mod tests {
         ).await;
 
         let req = test::TestRequest::get()
-            .uri(&format!(
-                "/?start_lat=13.6929&start_lng=-89.2182&end_lat=13.7084&end_lng=-89.1821"
-            ))
+            .uri("/?start_lat=13.6929&start_lng=-89.2182&end_lat=13.7084&end_lng=-89.1821")
             .to_request();
         
         let resp = test::call_service(&app, req).await;
-        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR); // El planificador no está inicializado
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE); // El planificador no está inicializado
 
-        let body: PlanningResponse = test::read_body_json(resp).await;
+        let body: ErrorResponse = test::read_body_json(resp).await;
         assert!(!body.success);
-        assert!(body.message.is_some());
-        assert!(body.routes.is_none());
+        assert_eq!(body.code, "planner_unavailable");
     }
 
     #[actix_web::test]
-    async fn test_coordinate_validation() {
-        assert!(is_valid_coordinates(13.6929, -89.2182)); // San Salvador
-        assert!(!is_valid_coordinates(0.0, 0.0));         // Fuera de El Salvador
-        assert!(!is_valid_coordinates(15.0, -89.0));      // Fuera de El Salvador
+    async fn test_admin_area_without_planner() {
+        let app = test::init_service(
+            App::new().service(web::resource("/").route(web::get().to(admin_area)))
+        ).await;
+
+        let req = test::TestRequest::get()
+            .uri("/?lat=13.6929&lng=-89.2182")
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert!(!body.success);
+        assert_eq!(body.code, "planner_unavailable");
+    }
+
+    #[actix_web::test]
+    async fn test_admin_area_invalid_coordinates() {
+        let app = test::init_service(
+            App::new().service(web::resource("/").route(web::get().to(admin_area)))
+        ).await;
+
+        for uri in ["/?lat=95.0&lng=-89.2182", "/?lat=13.6929&lng=NaN"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+            let body: ErrorResponse = test::read_body_json(resp).await;
+            assert_eq!(body.code, "invalid_coordinates");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_database_unavailable() {
+        // Sin pool registrado
+        let app = test::init_service(
+            App::new().service(web::resource("/").route(web::get().to(find_places)))
+        ).await;
+        let req = test::TestRequest::get().uri("/?name=izalco").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Con un pool que no puede conectarse
+        let config = geo_reader::db::DbConfig {
+            url: "postgres://geo@127.0.0.1:1/geo".into(),
+            max_size: 2,
+            wait_timeout: std::time::Duration::from_millis(500),
+            connect_timeout: std::time::Duration::from_millis(500),
+            verify_connections: false,
+        };
+        let pool = geo_reader::db::create_pool(&config).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .service(web::resource("/").route(web::get().to(find_places)))
+        ).await;
+        let req = test::TestRequest::get().uri("/?name=izalco").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(body.code, "database_unavailable");
+    }
+
+    #[actix_web::test]
+    async fn test_health_and_readiness() {
+        let app = test::init_service(
+            App::new().configure(crate::api::config)
+        ).await;
+
+        let req = test::TestRequest::get().uri("/health").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        // Sin planificador cargado no está listo; sin pool la base no cuenta como falla
+        let req = test::TestRequest::get().uri("/ready").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: ReadinessResponse = test::read_body_json(resp).await;
+        assert!(!body.ready);
+        assert!(!body.checks["planner"].ok);
+        assert!(body.checks["database"].ok);
+
+        let req = test::TestRequest::get().uri("/api/status").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: StatusResponse = test::read_body_json(resp).await;
+        assert_eq!(body.version, env!("CARGO_PKG_VERSION"));
+        assert!(body.regions.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_batch_errors() {
+        let app = test::init_service(
+            App::new().configure(crate::api::config)
+        ).await;
+
+        let item = serde_json::json!({
+            "start_lat": 13.70895, "start_lng": -89.19336, "end_lat": 13.68074, "end_lng": -89.24372
+        });
+
+        let req = test::TestRequest::post()
+            .uri("/api/plan_routes/batch")
+            .set_json(serde_json::json!({ "items": [item] }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let items = vec![item; DEFAULT_BATCH_MAX_ITEMS + 1];
+        let req = test::TestRequest::post()
+            .uri("/api/plan_routes/batch")
+            .set_json(serde_json::json!({ "items": items }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(body.code, "batch_too_large");
+
+        let req = test::TestRequest::post()
+            .uri("/api/plan_routes/batch")
+            .insert_header(("content-type", "application/json"))
+            .set_payload(r#"{"items": [{"start_lat": "x"}]}"#)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(body.code, "invalid_body");
+    }
+
+    #[actix_web::test]
+    async fn test_matrix_errors() {
+        let app = test::init_service(
+            App::new().configure(crate::api::config)
+        ).await;
+
+        let point = serde_json::json!({ "lat": 13.70895, "lng": -89.19336 });
+        let req = test::TestRequest::post()
+            .uri("/api/matrix")
+            .set_json(serde_json::json!({ "origins": [point], "destinations": [point] }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let many = vec![point.clone(); 101];
+        let req = test::TestRequest::post()
+            .uri("/api/matrix")
+            .set_json(serde_json::json!({ "origins": many, "destinations": many }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(body.code, "matrix_too_large");
+
+        let req = test::TestRequest::post()
+            .uri("/api/matrix")
+            .set_json(serde_json::json!({ "origins": [{ "lat": 95.0, "lng": 0.0 }], "destinations": [point] }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_routes_as_geojson() {
+        let routes = serde_json::to_value(vec![Route {
+            route_id: 7,
+            bus_id: 3,
+            direction_id: None,
+            route_geometry: serde_json::json!({ "type": "LineString", "coordinates": [[-89.2, 13.7], [-89.1, 13.7]] }),
+            distance: Some(120.0),
+            number_route: "101".into(),
+            code_route: "AB101".into(),
+            fees: Some(0.25),
+            special_fees: None,
+            first_trip: Some(std::time::SystemTime::UNIX_EPOCH),
+            last_trip: None,
+            frequency: Some("10".into()),
+            photo_url: None,
+        }])
+        .unwrap();
+
+        let resp = routes_response(&routes, Some(ResponseFormat::Geojson)).unwrap();
+        assert_eq!(resp.headers().get("content-type").unwrap(), "application/geo+json");
+        let body: Value = serde_json::from_slice(&actix_web::body::to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["type"], "FeatureCollection");
+        let feature = &body["features"][0];
+        assert_eq!(feature["geometry"]["type"], "LineString");
+        assert_eq!(feature["properties"]["number"], "101");
+        assert_eq!(feature["properties"]["fees"], 0.25);
+    }
+
+    #[actix_web::test]
+    async fn test_metrics_endpoint() {
+        let app = test::init_service(
+            App::new()
+                .wrap(crate::middlewares::logger::RequestLogger)
+                .configure(crate::api::config)
+        ).await;
+
+        let req = test::TestRequest::get().uri("/health").to_request();
+        test::call_service(&app, req).await;
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        assert!(body.contains(r#"geo_reader_http_requests_total{method="GET",route="/health",status="200"}"#));
+        assert!(body.contains(r#"geo_reader_cache_entries{cache="db"}"#));
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_query_is_json() {
+        let app = test::init_service(
+            App::new().configure(crate::api::config)
+        ).await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/plan_routes?start_lat=abc")
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let body: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(body.code, "invalid_query");
     }
 }
\ No newline at end of file
@@ -1,14 +1,30 @@
 use actix_web::web;
 
+pub mod error;
 pub mod handlers;
+pub mod openapi;
 use handlers::{
+    admin_area,
+    cache_stats,
+    db_pool_status,
     find_bus_route, 
     find_places, 
     get_nearby_routes_endpoint, 
     get_routes_by_number_endpoint, 
-    plan_routes
+    health,
+    list_regions,
+    plan_routes,
+    plan_routes_batch,
+    prometheus_metrics,
+    ready,
+    status,
+    travel_matrix,
+    vector_tiles
 };
 
+/// Tamaño máximo de un cuerpo JSON (lotes de planificación).
+const JSON_BODY_LIMIT: usize = 4 * 1024 * 1024;
+
 /// Inicialización del planificador de rutas
 pub async fn init() -> Result<(), Box<dyn std::error::Error>> {
     use handlers::initialize_planner;
@@ -17,13 +33,36 @@ pub async fn init() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/health", web::get().to(health))
+        .route("/ready", web::get().to(ready))
+        .route("/metrics", web::get().to(prometheus_metrics));
+    cfg.service(
+        web::scope("/tiles")
+            .app_data(web::QueryConfig::default().error_handler(error::query_error_handler))
+            .route("/{z}/{x}/{y}.mvt", web::get().to(vector_tiles)),
+    );
     cfg.service(
         web::scope("/api")
+            .app_data(web::QueryConfig::default().error_handler(error::query_error_handler))
+            .app_data(
+                web::JsonConfig::default()
+                    .limit(JSON_BODY_LIMIT)
+                    .error_handler(error::json_error_handler),
+            )
             .route("/places", web::get().to(find_places))
             .route("/nearby_routes", web::get().to(get_nearby_routes_endpoint))
             .route("/by_number", web::get().to(get_routes_by_number_endpoint))
             .route("/bus_route", web::get().to(find_bus_route))
-            .route("/plan_routes", web::get().to(plan_routes)),
+            .route("/plan_routes", web::get().to(plan_routes))
+            .route("/plan_routes/batch", web::post().to(plan_routes_batch))
+            .route("/matrix", web::post().to(travel_matrix))
+            .route("/admin_area", web::get().to(admin_area))
+            .route("/regions", web::get().to(list_regions))
+            .route("/db/pool", web::get().to(db_pool_status))
+            .route("/cache", web::get().to(cache_stats))
+            .route("/status", web::get().to(status))
+            .route("/openapi.json", web::get().to(openapi::openapi_json))
+            .route("/docs", web::get().to(openapi::docs)),
     );
 }
 
@@ -45,7 +84,13 @@ mod tests {
             .to_request();
         let resp = test::call_service(&app, req).await;
         
-        // Debería devolver error interno ya que el planificador no está inicializado en tests
-        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        // Debería devolver 503 ya que el planificador no está inicializado en tests
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Las teselas validan z/x/y antes de buscar el planificador
+        let req = test::TestRequest::get().uri("/tiles/2/4/0.mvt").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+        let req = test::TestRequest::get().uri("/tiles/14/4133/7595.mvt").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::SERVICE_UNAVAILABLE);
     }
 }
\ No newline at end of file
@@ -0,0 +1,119 @@
+use actix_web::{HttpResponse, Responder};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use super::handlers;
+
+/// Especificación OpenAPI generada a partir de los handlers y sus tipos.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "geo-reader API",
+        description = "Planificación de rutas de bus y consultas geográficas."
+    ),
+    paths(
+        handlers::plan_routes,
+        handlers::plan_routes_batch,
+        handlers::travel_matrix,
+        handlers::vector_tiles,
+        handlers::admin_area,
+        handlers::list_regions,
+        handlers::find_places,
+        handlers::get_nearby_routes_endpoint,
+        handlers::get_routes_by_number_endpoint,
+        handlers::find_bus_route,
+        handlers::db_pool_status,
+        handlers::cache_stats,
+        handlers::status,
+        handlers::health,
+        handlers::ready,
+        handlers::prometheus_metrics,
+    ),
+    components(schemas(super::error::ErrorResponse)),
+    modifiers(&ApiKeyScheme),
+    security(("api_key" = [])),
+    tags(
+        (name = "planner", description = "Planificador en memoria cargado desde los datos de cada región"),
+        (name = "database", description = "Consultas directas a PostgreSQL/PostGIS"),
+        (name = "status", description = "Salud, disponibilidad y versión de los datos"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Llave opcional en `X-API-Key`; ver `api_keys.example.toml`.
+struct ApiKeyScheme;
+
+impl Modify for ApiKeyScheme {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+            );
+        }
+    }
+}
+
+// Swagger UI se carga desde el CDN; solo necesita la URL de la especificación
+const DOCS_PAGE: &str = r##"<!DOCTYPE html>
+<html lang="es">
+<head>
+  <meta charset="utf-8">
+  <title>geo-reader API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/api/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"##;
+
+pub async fn openapi_json() -> impl Responder {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+pub async fn docs() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(DOCS_PAGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_covers_endpoints() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        for path in [
+            "/api/plan_routes",
+            "/api/admin_area",
+            "/api/regions",
+            "/api/places",
+            "/api/nearby_routes",
+            "/api/by_number",
+            "/api/bus_route",
+            "/api/status",
+            "/health",
+            "/ready",
+            "/metrics",
+            "/tiles/{z}/{x}/{y}.mvt",
+        ] {
+            assert!(spec["paths"][path]["get"].is_object(), "falta {}", path);
+        }
+        assert!(spec["paths"]["/api/plan_routes/batch"]["post"].is_object());
+        assert!(spec["paths"]["/api/matrix"]["post"].is_object());
+
+        let schemas = &spec["components"]["schemas"];
+        for schema in ["PlanningResponse", "RoutePlanResponse", "RouteSegmentResponse", "Route", "AdminArea"] {
+            assert!(schemas[schema].is_object(), "falta el esquema {}", schema);
+        }
+    }
+}
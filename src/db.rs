@@ -1,19 +1,132 @@
+use deadpool_postgres::{ManagerConfig, Pool, RecyclingMethod, Runtime};
+use serde::Serialize;
+use utoipa::ToSchema;
 use tokio_postgres::{Client, NoTls, Error};
+use log::error;
 use std::env;
+use std::time::Duration;
 use dotenv::dotenv;
 
-/// Establece la conexión con la base de datos PostgreSQL.
-pub async fn connect_to_db() -> Result<Client, Error> {
-    dotenv().ok();
-    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL debe estar configurada en .env");
+/// Abre una conexión suelta, fuera del pool, con la configuración de `DbConfig::from_env`.
+/// Para los endpoints usar el pool; esto queda para cargas puntuales (PostGIS, grafo).
+pub async fn connect_to_db() -> Result<Client, DbError> {
+    let config = DbConfig::from_env()?;
+    let mut pg_config: tokio_postgres::Config = config.url.parse()?;
+    pg_config.connect_timeout(config.connect_timeout);
 
-    let (client, connection) = tokio_postgres::connect(&db_url, NoTls).await?;
+    let (client, connection) = pg_config.connect(NoTls).await.map_err(DbError::Connect)?;
 
     tokio::spawn(async move {
         if let Err(e) = connection.await {
-            eprintln!("Error en la conexión: {}", e);
+            error!("Database connection error: {}", e);
         }
     });
 
     Ok(client)
 }
+
+#[derive(Debug, thiserror::Error)]
+pub enum DbError {
+    #[error("DATABASE_URL is not set")]
+    MissingUrl,
+    #[error("Invalid database config: {0}")]
+    Config(String),
+    #[error("Invalid DATABASE_URL: {0}")]
+    Url(#[from] Error),
+    #[error("Could not connect to database: {0}")]
+    Connect(Error),
+    #[error("Database query failed: {0}")]
+    Query(Error),
+    #[error("Could not build connection pool: {0}")]
+    Build(#[from] deadpool_postgres::BuildError),
+}
+
+/// Parámetros del pool de conexiones, leídos de variables de entorno.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbConfig {
+    pub url: String,
+    /// `DB_POOL_MAX_SIZE`: conexiones abiertas como máximo
+    pub max_size: usize,
+    /// `DB_POOL_WAIT_TIMEOUT_MS`: espera máxima por una conexión libre
+    pub wait_timeout: Duration,
+    /// `DB_CONNECT_TIMEOUT_MS`: tiempo máximo para abrir una conexión nueva
+    pub connect_timeout: Duration,
+    /// `DB_POOL_VERIFY`: ejecuta una consulta de prueba antes de reutilizar una conexión
+    pub verify_connections: bool,
+}
+
+impl DbConfig {
+    pub fn from_env() -> Result<Self, DbError> {
+        dotenv().ok();
+        let url = env::var("DATABASE_URL").map_err(|_| DbError::MissingUrl)?;
+
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> Result<T, DbError> {
+            match env::var(name) {
+                Ok(value) => value
+                    .parse()
+                    .map_err(|_| DbError::Config(format!("{} has an invalid value: {}", name, value))),
+                Err(_) => Ok(default),
+            }
+        }
+
+        Ok(Self {
+            url,
+            max_size: var("DB_POOL_MAX_SIZE", num_cpus::get() * 4)?,
+            wait_timeout: Duration::from_millis(var("DB_POOL_WAIT_TIMEOUT_MS", 5000)?),
+            connect_timeout: Duration::from_millis(var("DB_CONNECT_TIMEOUT_MS", 5000)?),
+            verify_connections: var("DB_POOL_VERIFY", false)?,
+        })
+    }
+}
+
+/// Crea el pool. Las conexiones se abren bajo demanda, así que no falla si la base
+/// todavía no está disponible. Cada conexión guarda sus sentencias preparadas
+/// (`prepare_cached`) mientras vive en el pool.
+pub fn create_pool(config: &DbConfig) -> Result<Pool, DbError> {
+    let mut pg_config: tokio_postgres::Config = config.url.parse()?;
+    pg_config.connect_timeout(config.connect_timeout);
+
+    let manager = deadpool_postgres::Manager::from_config(
+        pg_config,
+        NoTls,
+        ManagerConfig {
+            recycling_method: if config.verify_connections {
+                RecyclingMethod::Verified
+            } else {
+                RecyclingMethod::Fast
+            },
+        },
+    );
+
+    Ok(Pool::builder(manager)
+        .max_size(config.max_size)
+        .runtime(Runtime::Tokio1)
+        .wait_timeout(Some(config.wait_timeout))
+        .create_timeout(Some(config.connect_timeout))
+        .recycle_timeout(Some(config.connect_timeout))
+        .build()?)
+}
+
+/// Estado del pool para monitoreo.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PoolStatus {
+    pub max_size: usize,
+    /// Conexiones abiertas (libres y en uso)
+    pub size: usize,
+    /// Conexiones libres
+    pub available: usize,
+    /// Peticiones esperando una conexión
+    pub waiting: usize,
+}
+
+impl PoolStatus {
+    pub fn of(pool: &Pool) -> Self {
+        let status = pool.status();
+        Self {
+            max_size: status.max_size,
+            size: status.size,
+            available: status.available,
+            waiting: status.waiting,
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Geo Reader: planificación de rutas de transporte colectivo y consultas geográficas.
+//!
+//! Lo que se re-exporta en la raíz es la API estable que usan el servidor, los scripts
+//! y la consola; los módulos internos (`queries`, `plan_routes`, ...) siguen públicos
+//! pero pueden cambiar.
+
+pub mod db;
+pub mod metrics;
+pub mod queries;
+pub mod utils;
+pub mod algorithms;
+
+pub use queries::*;
+pub use utils::*;
+pub use algorithms::*;
+
+// Planificador
+pub use queries::plan_routes::{
+    _structs::{
+        BusStopProperties, DepartmentProperties, GeoJsonFeature, GeoJsonFeatureCollection,
+        GeoJsonGeometry, MunicipalityProperties, RoutePlan, RouteProperties, RouteSegment,
+        TransferPoint, TransferType,
+    },
+    export::{feature_collection, plan_features, route_features},
+    index::{PlanExplanation, PlanningConfig, PlanningError, RoutePlanner, ScoredPlan},
+    matrix::{MatrixCell, TravelMatrix},
+    region::{PlannerRegistry, Region, RegionConfig, RegionError, RegionsConfig},
+    snapshot::{PlannerSnapshot, SnapshotError},
+    spatial_search::{SearchError, SpatialSearch},
+    tiles::{vector_tile, TileId},
+};
+
+// Validación geográfica
+pub use queries::plan_routes::geo_validation::{
+    AdminArea, GeoValidator, ServiceAreaConfig, ValidationError, ValidationResult,
+};
+
+// Carga de datos
+pub use queries::plan_routes::{
+    data_loader::{DataLoader, LoadSummary, LoaderError},
+    data_source::{DataSourceKind, PlannerDataSource},
+    manifest::{DataManifest, LayerConfig, LayerKind},
+    postgis::PostgisDataSource,
+    quality::{QualityConfig, QualityReport},
+};
+
+// Caché de resultados
+pub use utils::result_cache::{CacheConfig, CacheStats, ResultCache};
+
+// Consultas a la base de datos
+pub use db::{connect_to_db, create_pool, DbConfig, DbError, PoolStatus};
+pub use queries::{
+    _structs::Route,
+    find_by_coordinates::find_by_coordinates,
+    find_by_number::get_routes_by_number,
+    find_bus_route::{calculate_route, RouteResult},
+    find_nearby::find_nearby_places,
+    find_places::find_places_by_name,
+    find_route::find_route,
+    nearby_route::get_nearby_routes,
+};
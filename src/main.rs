@@ -1,47 +1,90 @@
-use actix_web::{App, HttpServer};
+use actix_web::{middleware::Compress, web, App, HttpServer};
 use dotenv::dotenv;
 use std::env;
-use env_logger::Env;
-use log::{info, error};
+use geo_reader::db::{create_pool, DbConfig, DbError};
+use log::{info, error, warn};
 
-mod db;
 mod api;
 mod middlewares;
 
-pub mod queries;
-pub mod utils;
-pub mod algorithms;
-
-pub use queries::*;
-pub use utils::*;
-pub use algorithms::*;
-
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    // Inicializar el logger
-    env_logger::init_from_env(Env::default().default_filter_or("info"));
-    
     // Cargar variables de entorno
     dotenv().ok();
 
-    // Inicializar el planificador de rutas
-    if let Err(e) = api::handlers::initialize_planner().await {
-        error!("Failed to initialize route planner: {}", e);
-        std::process::exit(1);
+    // Inicializar los logs; también recibe lo que se escribe con `log`
+    init_tracing();
+
+    api::handlers::mark_started();
+
+    // Inicializar el planificador de rutas; si falla el servidor sigue arriba
+    // y `/ready` reporta el error
+    match api::init().await {
+        Ok(()) => info!("Route planner initialized successfully"),
+        Err(e) => error!("Failed to initialize route planner: {}", e),
     }
-    info!("Route planner initialized successfully");
+
+    // Pool de conexiones compartido por los endpoints que consultan la base
+    let pool = match DbConfig::from_env().and_then(|config| create_pool(&config)) {
+        Ok(pool) => {
+            info!("Database pool ready (max {} connections)", pool.status().max_size);
+            Some(web::Data::new(pool))
+        }
+        Err(DbError::MissingUrl) => {
+            warn!("DATABASE_URL is not set, database endpoints will return 503");
+            None
+        }
+        Err(e) => {
+            error!("Failed to create database pool: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Llaves de API y límites de peticiones; los baldes se comparten entre workers
+    let api_keys = match middlewares::api_key::ApiKeysConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to load API keys config: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let limiter = middlewares::rate_limit::RateLimiter::new();
 
     let port = env::var("PORT").unwrap_or_else(|_| "8087".to_string());
     let addr = format!("0.0.0.0:{}", port);
     
     info!("Starting server on {}", addr);
 
-    HttpServer::new(|| {
-        App::new()
+    HttpServer::new(move || {
+        let mut app = App::new()
+            .wrap(middlewares::rate_limit::RateLimit::new(limiter.clone()))
+            .wrap(middlewares::api_key::ApiKeyAuth::new(api_keys.clone(), limiter.clone()))
+            // Por fuera de la autenticación para que sus errores también se conviertan
+            .wrap(middlewares::encoding::ContentNegotiation)
             .wrap(middlewares::logger::RequestLogger)  // Agregar el middleware
-            .configure(api::config)
+            // gzip, brotli o zstd según `Accept-Encoding`, sobre la representación final
+            .wrap(Compress::default());
+        if let Some(pool) = &pool {
+            app = app.app_data(pool.clone());
+        }
+        app.configure(api::config)
     })
     .bind(&addr)?
     .run()
     .await
+}
+
+/// `RUST_LOG` filtra (por defecto `info`) y `LOG_FORMAT=json` escribe una línea JSON
+/// por evento, con los campos del span de la petición.
+fn init_tracing() {
+    use tracing_subscriber::EnvFilter;
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    let json = env::var("LOG_FORMAT").map(|format| format.eq_ignore_ascii_case("json")).unwrap_or(false);
+    if json {
+        builder.json().with_current_span(true).with_span_list(false).init();
+    } else {
+        builder.init();
+    }
 }
\ No newline at end of file
@@ -1,116 +1,199 @@
-use geo_reader::{find_places_by_name, find_nearby_places, find_by_coordinates, find_route_as_geojson};
-use tokio;
 use dotenv::dotenv;
+use geo_reader::{
+    create_pool, feature_collection, find_by_coordinates, find_nearby_places, find_places_by_name,
+    find_route, get_routes_by_number, route_features, DbConfig, PlannerRegistry, RegionsConfig,
+};
+use geo_types::Point;
+use serde_json::{json, Value};
 use std::env;
-use std::io::{self, Write};
+use std::process::ExitCode;
 
-#[tokio::main]
-async fn main() {
-    // Cargar las variables desde el archivo .env
-    dotenv().ok();
+const USAGE: &str = "Uso: geo-reader-console <COMANDO> [ARGS]
 
-    loop {
-        println!("Geo Reader Console App");
-        println!("1. Find places by name");
-        println!("2. Find nearby places");
-        println!("3. Find by coordinates");
-        println!("4. Find route as GeoJSON");
-        println!("5. Exit");
-        print!("Choose an option: ");
-        io::stdout().flush().unwrap();
-
-        let mut choice = String::new();
-        io::stdin().read_line(&mut choice).unwrap();
-        let choice = choice.trim();
-
-        match choice {
-            "1" => {
-                print!("Enter place name: ");
-                io::stdout().flush().unwrap();
-                let mut name = String::new();
-                io::stdin().read_line(&mut name).unwrap();
-                let name = name.trim();
-
-                match find_places_by_name(name).await {
-                    Ok(places) => {
-                        for (name, longitude, latitude) in places {
-                            println!(
-                                "Found: {} (Longitude: {}, Latitude: {})",
-                                name, longitude, latitude
-                            );
-                        }
-                    }
-                    Err(e) => eprintln!("Error: {}", e),
-                }
-            }
-            "2" => {
-                if let Some((lat, lon, radius)) = read_coordinates_and_radius() {
-                    match find_nearby_places(lat, lon, radius).await {
-                        Ok(places) => {
-                            for place in places {
-                                println!("Nearby: {}", place);
-                            }
-                        }
-                        Err(e) => eprintln!("Error: {}", e),
-                    }
-                }
-            }
-            "3" => {
-                if let Some((lat, lon, _)) = read_coordinates_and_radius() {
-                    match find_by_coordinates(lat, lon).await {
-                        Ok(places) => {
-                            for place in places {
-                                println!("Exact match: {}", place);
-                            }
-                        }
-                        Err(e) => eprintln!("Error: {}", e),
-                    }
-                }
-            }
-            "4" => {
-                match read_coordinates_from_env() {
-                    Some((start_lat, start_lon, end_lat, end_lon)) => {
-                        match find_route_as_geojson(start_lat, start_lon, end_lat, end_lon).await {
-                            Ok(geojson) => println!("GeoJSON Route: {}", geojson),
-                            Err(e) => eprintln!("Error: {}", e),
-                        }
-                    }
-                    None => eprintln!("Error: Could not read coordinates from environment."),
-                }
-            }
-            "5" => break,
-            _ => println!("Invalid choice, please try again."),
+Comandos:
+  places <NOMBRE>                      Lugares cuyo nombre contiene NOMBRE
+  nearby <LAT> <LNG> [--radius M]      Lugares a M metros o menos (por defecto 500)
+  at <LAT> <LNG>                       Lugar en las coordenadas exactas
+  number <NUMERO>                      Rutas de bus por número
+  route <LAT,LNG> <LAT,LNG>            Rutas de la base que pasan por ambos puntos, como GeoJSON
+  plan <LAT,LNG> <LAT,LNG> [--region ID]  Planifica con los datos del servidor
+  area <LAT> <LNG> [--region ID]       Departamento y municipio del punto";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Places(String),
+    Nearby { lat: f64, lng: f64, radius: f64 },
+    At { lat: f64, lng: f64 },
+    Number(String),
+    Route { from: Point<f64>, to: Point<f64> },
+    Plan { from: Point<f64>, to: Point<f64>, region: Option<String> },
+    Area { point: Point<f64>, region: Option<String> },
+}
+
+fn parse_number(value: &str) -> Result<f64, String> {
+    value.parse().map_err(|_| format!("Número inválido: {}", value))
+}
+
+/// `LAT,LNG` -> punto (x = longitud).
+fn parse_point(value: &str) -> Result<Point<f64>, String> {
+    let (lat, lng) = value
+        .split_once(',')
+        .ok_or_else(|| format!("Coordenada inválida (se espera LAT,LNG): {}", value))?;
+    Ok(Point::new(parse_number(lng.trim())?, parse_number(lat.trim())?))
+}
+
+fn parse_command(args: &[String]) -> Result<Command, String> {
+    let (name, rest) = args.split_first().ok_or_else(|| USAGE.to_string())?;
+
+    // Separa las opciones `--clave valor` de los argumentos posicionales
+    let mut positional = Vec::new();
+    let mut radius = 500.0;
+    let mut region = None;
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| format!("Falta el valor de {}", arg));
+        match arg.as_str() {
+            "--radius" => radius = parse_number(value()?)?,
+            "--region" => region = Some(value()?.clone()),
+            _ => positional.push(arg.as_str()),
         }
     }
+
+    let command = match (name.as_str(), positional.as_slice()) {
+        ("places", [_, ..]) => Command::Places(positional.join(" ")),
+        ("nearby", [lat, lng]) => Command::Nearby { lat: parse_number(lat)?, lng: parse_number(lng)?, radius },
+        ("at", [lat, lng]) => Command::At { lat: parse_number(lat)?, lng: parse_number(lng)? },
+        ("number", [number]) => Command::Number(number.to_string()),
+        ("route", [from, to]) => Command::Route { from: parse_point(from)?, to: parse_point(to)? },
+        ("plan", [from, to]) => Command::Plan { from: parse_point(from)?, to: parse_point(to)?, region },
+        ("area", [lat, lng]) => Command::Area {
+            point: Point::new(parse_number(lng)?, parse_number(lat)?),
+            region,
+        },
+        _ => return Err(USAGE.to_string()),
+    };
+    Ok(command)
+}
+
+async fn db_client() -> Result<deadpool_postgres::Client, Box<dyn std::error::Error>> {
+    let config = DbConfig {
+        max_size: 1,
+        ..DbConfig::from_env()?
+    };
+    Ok(create_pool(&config)?.get().await?)
+}
+
+async fn load_registry() -> Result<PlannerRegistry, Box<dyn std::error::Error>> {
+    let config = RegionsConfig::from_env()?;
+    Ok(PlannerRegistry::build(&config).await?)
 }
 
-fn read_coordinates_and_radius() -> Option<(f64, f64, f64)> {
-    let lat = prompt_for_float("Enter latitude: ")?;
-    let lon = prompt_for_float("Enter longitude: ")?;
-    let radius = prompt_for_float("Enter radius (in meters): ")?;
-    Some((lat, lon, radius))
+async fn run(command: Command) -> Result<Value, Box<dyn std::error::Error>> {
+    let output = match command {
+        Command::Places(name) => {
+            let client = db_client().await?;
+            let places = find_places_by_name(&name, &client).await?;
+            places
+                .into_iter()
+                .map(|(name, longitude, latitude)| json!({ "name": name, "latitude": latitude, "longitude": longitude }))
+                .collect()
+        }
+        Command::Nearby { lat, lng, radius } => {
+            let client = db_client().await?;
+            json!(find_nearby_places(lat, lng, radius, &client).await?)
+        }
+        Command::At { lat, lng } => {
+            let client = db_client().await?;
+            json!(find_by_coordinates(lat, lng, &client).await?)
+        }
+        Command::Number(number) => {
+            let client = db_client().await?;
+            json!(get_routes_by_number(number, &client).await?)
+        }
+        Command::Route { from, to } => {
+            let client = db_client().await?;
+            let routes = find_route(from.y(), from.x(), to.y(), to.x(), &client).await?;
+            // Mismas features que `format=geojson` en la API
+            feature_collection(route_features(&routes))
+        }
+        Command::Plan { from, to, region } => {
+            let registry = load_registry().await?;
+            let region = registry.resolve(region.as_deref(), from)?;
+            json!({
+                "region": region.config.id,
+                "plans": region.planner.plan_route(from, to)?,
+            })
+        }
+        Command::Area { point, region } => {
+            let registry = load_registry().await?;
+            let region = registry.resolve(region.as_deref(), point)?;
+            json!({
+                "region": region.config.id,
+                "area": region.planner.validator().locate(point),
+            })
+        }
+    };
+    Ok(output)
 }
 
-fn prompt_for_float(prompt: &str) -> Option<f64> {
-    print!("{}", prompt);
-    io::stdout().flush().unwrap();
-
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    match input.trim().parse::<f64>() {
-        Ok(value) => Some(value),
-        Err(_) => {
-            eprintln!("Invalid input. Please enter a valid number.");
-            None
+#[tokio::main]
+async fn main() -> ExitCode {
+    // Cargar las variables desde el archivo .env
+    dotenv().ok();
+    // Los logs van a stderr para no mezclarse con el JSON de la salida
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("warn"));
+    tracing_subscriber::fmt().with_env_filter(filter).with_writer(std::io::stderr).init();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = match parse_command(&args) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(command).await {
+        Ok(output) => {
+            println!("{}", serde_json::to_string_pretty(&output).unwrap_or_default());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
         }
     }
 }
 
-fn read_coordinates_from_env() -> Option<(f64, f64, f64, f64)> {
-    let start_lat = env::var("START_LAT").ok()?.parse::<f64>().ok()?;
-    let start_lon = env::var("START_LON").ok()?.parse::<f64>().ok()?;
-    let end_lat = env::var("END_LAT").ok()?.parse::<f64>().ok()?;
-    let end_lon = env::var("END_LON").ok()?.parse::<f64>().ok()?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Some((start_lat, start_lon, end_lat, end_lon))
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            parse_command(&args("places Metrocentro San Salvador")).unwrap(),
+            Command::Places("Metrocentro San Salvador".into())
+        );
+        assert_eq!(
+            parse_command(&args("nearby 13.69 -89.19 --radius 250")).unwrap(),
+            Command::Nearby { lat: 13.69, lng: -89.19, radius: 250.0 }
+        );
+        assert_eq!(
+            parse_command(&args("plan 13.69,-89.19 13.70,-89.25 --region amss")).unwrap(),
+            Command::Plan {
+                from: Point::new(-89.19, 13.69),
+                to: Point::new(-89.25, 13.70),
+                region: Some("amss".into()),
+            }
+        );
+        assert!(parse_command(&args("route 13.69,-89.19")).is_err());
+        assert!(parse_command(&args("nearby 13.69 abc")).is_err());
+        assert!(parse_command(&[]).is_err());
+    }
 }
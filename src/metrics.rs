@@ -0,0 +1,149 @@
+//! Métricas en formato Prometheus que expone el servidor en `/metrics`.
+//!
+//! Las métricas viven en un registro propio; la librería registra los tiempos del
+//! planificador y el servidor agrega peticiones, consultas a la base y cachés.
+
+use lazy_static::lazy_static;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::time::Instant;
+
+use crate::utils::result_cache::CacheStats;
+
+/// Cubetas de latencia en segundos, de 1ms a 10s.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+lazy_static! {
+    pub static ref REGISTRY: Registry = Registry::new_custom(Some("geo_reader".into()), None).unwrap();
+
+    pub static ref HTTP_REQUESTS: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("http_requests_total", "HTTP requests by route, method and status"),
+        &["route", "method", "status"],
+    ));
+    pub static ref HTTP_REQUEST_DURATION: HistogramVec = register(HistogramVec::new(
+        HistogramOpts::new("http_request_duration_seconds", "HTTP request latency")
+            .buckets(LATENCY_BUCKETS.to_vec()),
+        &["route", "method", "status"],
+    ));
+    pub static ref PLANNER_STAGE_DURATION: HistogramVec = register(HistogramVec::new(
+        HistogramOpts::new("planner_stage_duration_seconds", "Route planner time per stage")
+            .buckets(LATENCY_BUCKETS.to_vec()),
+        &["stage"],
+    ));
+    pub static ref PLANNER_CANDIDATE_PLANS: HistogramVec = register(HistogramVec::new(
+        HistogramOpts::new("planner_candidate_plans", "Candidate plans explored per search")
+            .buckets(vec![0.0, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 250.0, 500.0]),
+        &[],
+    ));
+    pub static ref DB_QUERY_DURATION: HistogramVec = register(HistogramVec::new(
+        HistogramOpts::new("db_query_duration_seconds", "Database query latency")
+            .buckets(LATENCY_BUCKETS.to_vec()),
+        &["query", "outcome"],
+    ));
+    pub static ref CACHE_ENTRIES: IntGaugeVec = register(IntGaugeVec::new(
+        Opts::new("cache_entries", "Entries stored in a result cache"),
+        &["cache"],
+    ));
+    pub static ref CACHE_CAPACITY: IntGaugeVec = register(IntGaugeVec::new(
+        Opts::new("cache_capacity", "Maximum entries of a result cache"),
+        &["cache"],
+    ));
+    // Se incrementan en cada consulta; sobreviven a los cachés que se reconstruyen al recargar
+    pub static ref CACHE_HITS: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("cache_hits_total", "Result cache hits"),
+        &["cache"],
+    ));
+    pub static ref CACHE_MISSES: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("cache_misses_total", "Result cache misses"),
+        &["cache"],
+    ));
+    pub static ref DB_POOL_CONNECTIONS: IntGaugeVec = register(IntGaugeVec::new(
+        Opts::new("db_pool_connections", "Database pool connections by state"),
+        &["state"],
+    ));
+}
+
+fn register<M: prometheus::core::Collector + Clone + 'static>(metric: prometheus::Result<M>) -> M {
+    let metric = metric.expect("invalid metric definition");
+    REGISTRY.register(Box::new(metric.clone())).expect("metric registered twice");
+    metric
+}
+
+/// Etapa del planificador medida en `planner_stage_duration_seconds`.
+#[derive(Debug, Clone, Copy)]
+pub enum PlannerStage {
+    Validation,
+    Search,
+    Scoring,
+    Matrix,
+}
+
+impl PlannerStage {
+    fn label(self) -> &'static str {
+        match self {
+            PlannerStage::Validation => "validation",
+            PlannerStage::Search => "search",
+            PlannerStage::Scoring => "scoring",
+            PlannerStage::Matrix => "matrix",
+        }
+    }
+}
+
+/// Ejecuta `f` y registra su duración como la etapa `stage`.
+pub fn time_stage<T>(stage: PlannerStage, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    PLANNER_STAGE_DURATION
+        .with_label_values(&[stage.label()])
+        .observe(start.elapsed().as_secs_f64());
+    result
+}
+
+pub fn observe_candidate_plans(count: usize) {
+    PLANNER_CANDIDATE_PLANS.with_label_values(&[]).observe(count as f64);
+}
+
+/// Contadores `cache_hits_total` y `cache_misses_total` del caché `name`.
+pub fn cache_counters(name: &str) -> (IntCounter, IntCounter) {
+    (CACHE_HITS.with_label_values(&[name]), CACHE_MISSES.with_label_values(&[name]))
+}
+
+/// Copia el tamaño de un caché a los gauges `cache_entries` y `cache_capacity`.
+pub fn set_cache_stats(name: &str, stats: &CacheStats) {
+    CACHE_ENTRIES.with_label_values(&[name]).set(stats.entries as i64);
+    CACHE_CAPACITY.with_label_values(&[name]).set(stats.capacity as i64);
+}
+
+/// Todas las métricas en el formato de texto de Prometheus.
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer) {
+        log::error!("Could not encode metrics: {}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_metrics() {
+        let plans = time_stage(PlannerStage::Search, || 3);
+        time_stage(PlannerStage::Matrix, || ());
+        observe_candidate_plans(plans);
+        set_cache_stats("test", &CacheStats { entries: 2, capacity: 8, ..Default::default() });
+        cache_counters("test").0.inc();
+
+        let text = render();
+        assert!(text.contains("geo_reader_planner_stage_duration_seconds_count{stage=\"search\"}"));
+        assert!(text.contains("geo_reader_planner_stage_duration_seconds_count{stage=\"matrix\"}"));
+        assert!(text.contains("geo_reader_planner_candidate_plans_sum"));
+        assert!(text.contains("geo_reader_cache_entries{cache=\"test\"} 2"));
+        assert!(text.contains("# TYPE geo_reader_cache_hits_total counter"));
+        assert!(text.contains("geo_reader_cache_hits_total{cache=\"test\"} 1"));
+    }
+}
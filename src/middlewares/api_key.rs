@@ -0,0 +1,437 @@
+// src/middlewares/api_key.rs
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{Error, HttpMessage};
+use futures::future::LocalBoxFuture;
+use log::info;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::future::{ready, Ready};
+use std::path::Path;
+use std::rc::Rc;
+
+use super::rate_limit::{rate_limited, RateLimiter};
+use crate::api::error::ApiError;
+
+/// Archivo de llaves usado si `API_KEYS_CONFIG` no está definida.
+const DEFAULT_API_KEYS_FILE: &str = "api_keys.toml";
+/// Encabezado con la llave del cliente.
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// Cuota de un cliente: `requests_per_minute` sostenido con ráfagas de hasta `burst`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Quota {
+    pub requests_per_minute: u32,
+    pub burst: Option<u32>,
+}
+
+impl Quota {
+    pub fn burst(&self) -> u32 {
+        self.burst.unwrap_or(self.requests_per_minute).max(1)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKeyConfig {
+    pub key: String,
+    /// Nombre del cliente para logs y métricas
+    pub name: String,
+    #[serde(flatten)]
+    pub quota: Quota,
+}
+
+/// Contenido de `api_keys.toml`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ApiKeysConfig {
+    /// Si es `true`, las peticiones sin llave válida reciben 401
+    #[serde(default)]
+    pub require_key: bool,
+    /// Usa `X-Forwarded-For` para identificar la IP (solo detrás de un proxy confiable)
+    #[serde(default)]
+    pub trust_forwarded_for: bool,
+    /// Cuota por IP, también para peticiones con llave; sin ella no hay límite por IP
+    pub anonymous: Option<Quota>,
+    /// Rutas que no pasan por autenticación ni límites
+    #[serde(default = "default_exempt_paths")]
+    pub exempt_paths: Vec<String>,
+    #[serde(default)]
+    pub keys: Vec<ApiKeyConfig>,
+}
+
+fn default_exempt_paths() -> Vec<String> {
+    ["/health", "/ready", "/metrics", "/api/docs", "/api/openapi.json"]
+        .iter()
+        .map(|path| path.to_string())
+        .collect()
+}
+
+impl ApiKeysConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// Lee `API_KEYS_CONFIG` o `api_keys.toml`. Sin archivo la API queda abierta y
+    /// sin límites, como antes.
+    pub fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
+        let path = env::var("API_KEYS_CONFIG").unwrap_or_else(|_| DEFAULT_API_KEYS_FILE.to_string());
+        if !Path::new(&path).exists() {
+            info!("No API keys config at {}, API is open and not rate limited", path);
+            return Ok(Self {
+                exempt_paths: default_exempt_paths(),
+                ..Self::default()
+            });
+        }
+
+        let config = Self::load(&path)?;
+        info!(
+            "Loaded {} API key(s) from {} (require_key: {})",
+            config.keys.len(),
+            path,
+            config.require_key
+        );
+        Ok(config)
+    }
+}
+
+/// Quién hace la petición; `ApiKeyAuth` lo deja en las extensiones de la petición.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Caller {
+    /// `ip_quota` es la cuota `[anonymous]`: una llave no evita el límite por IP
+    Key { name: String, quota: Quota, ip: String, ip_quota: Option<Quota> },
+    Anonymous { ip: String, quota: Option<Quota> },
+}
+
+/// Balde por IP; lo comparten las peticiones con llave, sin llave y rechazadas.
+fn ip_bucket(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+impl Caller {
+    /// Baldes de tokens que se cobran por cada petición, con su cuota. El primero
+    /// es el propio del cliente.
+    pub fn buckets(&self) -> Vec<(String, Quota)> {
+        match self {
+            Caller::Key { name, quota, ip, ip_quota } => {
+                let mut buckets = vec![(format!("key:{}", name), *quota)];
+                buckets.extend(ip_quota.map(|quota| (ip_bucket(ip), quota)));
+                buckets
+            }
+            Caller::Anonymous { ip, quota } => quota
+                .map(|quota| (ip_bucket(ip), quota))
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+/// Identifica al [`Caller`]. Las peticiones rechazadas por llave también se cobran
+/// en el balde de su IP con `limiter`, para que no se puedan probar llaves sin límite.
+pub struct ApiKeyAuth {
+    config: Rc<ApiKeysConfig>,
+    keys: Rc<HashMap<String, ApiKeyConfig>>,
+    limiter: RateLimiter,
+}
+
+impl ApiKeyAuth {
+    pub fn new(config: ApiKeysConfig, limiter: RateLimiter) -> Self {
+        let keys = config.keys.iter().map(|key| (key.key.clone(), key.clone())).collect();
+        Self {
+            config: Rc::new(config),
+            keys: Rc::new(keys),
+            limiter,
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ApiKeyAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = ApiKeyAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiKeyAuthMiddleware {
+            service,
+            config: self.config.clone(),
+            keys: self.keys.clone(),
+            limiter: self.limiter.clone(),
+        }))
+    }
+}
+
+pub struct ApiKeyAuthMiddleware<S> {
+    service: S,
+    config: Rc<ApiKeysConfig>,
+    keys: Rc<HashMap<String, ApiKeyConfig>>,
+    limiter: RateLimiter,
+}
+
+impl<S> ApiKeyAuthMiddleware<S> {
+    fn client_ip(&self, req: &ServiceRequest) -> String {
+        let info = req.connection_info();
+        let ip = if self.config.trust_forwarded_for {
+            info.realip_remote_addr()
+        } else {
+            info.peer_addr()
+        };
+        ip.unwrap_or("unknown").to_string()
+    }
+
+    fn caller(&self, req: &ServiceRequest) -> Result<Caller, ApiError> {
+        let key = req
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim);
+
+        match key {
+            Some(key) => self
+                .keys
+                .get(key)
+                .map(|key| Caller::Key {
+                    name: key.name.clone(),
+                    quota: key.quota,
+                    ip: self.client_ip(req),
+                    ip_quota: self.config.anonymous,
+                })
+                .ok_or(ApiError::InvalidApiKey),
+            None if self.config.require_key => Err(ApiError::MissingApiKey),
+            None => Ok(Caller::Anonymous {
+                ip: self.client_ip(req),
+                quota: self.config.anonymous,
+            }),
+        }
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for ApiKeyAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if self.config.exempt_paths.iter().any(|path| req.path() == path) {
+            let fut = self.service.call(req);
+            return Box::pin(async move { Ok(fut.await?.map_into_left_body()) });
+        }
+
+        match self.caller(&req) {
+            Ok(caller) => {
+                req.extensions_mut().insert(caller);
+                let fut = self.service.call(req);
+                Box::pin(async move { Ok(fut.await?.map_into_left_body()) })
+            }
+            Err(e) => {
+                // Sin llave válida no hay `Caller` para `RateLimit`: se cobra aquí la IP
+                if let Some(quota) = self.config.anonymous {
+                    let bucket = ip_bucket(&self.client_ip(&req));
+                    let decision = self.limiter.check_all(&[(bucket.clone(), quota)])[0];
+                    if !decision.allowed {
+                        let response = rate_limited(req, &bucket, &decision).map_into_right_body();
+                        return Box::pin(async move { Ok(response) });
+                    }
+                }
+                let response = req.error_response(e).map_into_right_body();
+                Box::pin(async move { Ok(response) })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middlewares::rate_limit::RateLimit;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, web, App, HttpResponse};
+
+    /// `code` que verá `RequestLogger` en la línea de acceso.
+    fn error_code<B>(resp: &ServiceResponse<B>) -> Option<&'static str> {
+        resp.response().error().and_then(|e| e.as_error::<ApiError>()).map(ApiError::code)
+    }
+
+    #[actix_web::test]
+    async fn test_api_keys_and_quotas() {
+        let config: ApiKeysConfig = toml::from_str(
+            r#"
+            require_key = true
+
+            [anonymous]
+            requests_per_minute = 60
+            burst = 1
+
+            [[keys]]
+            key = "secreta"
+            name = "movil"
+            requests_per_minute = 120
+            burst = 2
+            "#,
+        )
+        .unwrap();
+
+        let limiter = RateLimiter::new();
+        let app = test::init_service(
+            App::new()
+                .wrap(RateLimit::new(limiter.clone()))
+                .wrap(ApiKeyAuth::new(config, limiter))
+                .route("/api/regions", web::get().to(HttpResponse::Ok))
+                .route("/api/docs", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/regions")
+            .peer_addr("10.0.1.1:1234".parse().unwrap())
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::get()
+            .uri("/api/regions")
+            .peer_addr("10.0.1.2:1234".parse().unwrap())
+            .insert_header((API_KEY_HEADER, "otra"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+        // La documentación no necesita llave
+        let req = test::TestRequest::get().uri("/api/docs").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let keyed = |ip: &str| {
+            test::TestRequest::get()
+                .uri("/api/regions")
+                .peer_addr(format!("{}:1234", ip).parse().unwrap())
+                .insert_header((API_KEY_HEADER, "secreta"))
+                .to_request()
+        };
+        let resp = test::call_service(&app, keyed("10.0.0.1")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("ratelimit-limit").unwrap(), "120");
+        assert_eq!(resp.headers().get("ratelimit-remaining").unwrap(), "1");
+
+        // La IP agotó su cuota; el rechazo no le cuesta un token a la llave
+        let resp = test::call_service(&app, keyed("10.0.0.1")).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers().get("ratelimit-limit").unwrap(), "60");
+
+        let resp = test::call_service(&app, keyed("10.0.0.2")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("ratelimit-remaining").unwrap(), "0");
+
+        let resp = test::call_service(&app, keyed("10.0.0.3")).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers().get("ratelimit-limit").unwrap(), "120");
+        assert_eq!(resp.headers().get("retry-after").unwrap(), "1");
+    }
+
+    #[actix_web::test]
+    async fn test_api_keys_also_pay_per_ip() {
+        let config: ApiKeysConfig = toml::from_str(
+            r#"
+            [anonymous]
+            requests_per_minute = 60
+            burst = 1
+
+            [[keys]]
+            key = "secreta"
+            name = "movil"
+            requests_per_minute = 600
+            "#,
+        )
+        .unwrap();
+
+        let limiter = RateLimiter::new();
+        let app = test::init_service(
+            App::new()
+                .wrap(RateLimit::new(limiter.clone()))
+                .wrap(ApiKeyAuth::new(config, limiter))
+                .route("/api/regions", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let keyed = || {
+            test::TestRequest::get()
+                .uri("/api/regions")
+                .peer_addr("10.0.0.1:1234".parse().unwrap())
+                .insert_header((API_KEY_HEADER, "secreta"))
+                .to_request()
+        };
+        let resp = test::call_service(&app, keyed()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("ratelimit-limit").unwrap(), "600");
+
+        // La llave tiene cuota de sobra, pero la IP ya gastó la suya
+        let resp = test::call_service(&app, keyed()).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers().get("ratelimit-limit").unwrap(), "60");
+
+        let req = test::TestRequest::get()
+            .uri("/api/regions")
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_keys_pay_per_ip() {
+        let config: ApiKeysConfig = toml::from_str(
+            r#"
+            require_key = true
+
+            [anonymous]
+            requests_per_minute = 60
+            burst = 3
+
+            [[keys]]
+            key = "secreta"
+            name = "movil"
+            requests_per_minute = 600
+            "#,
+        )
+        .unwrap();
+
+        let limiter = RateLimiter::new();
+        let app = test::init_service(
+            App::new()
+                .wrap(RateLimit::new(limiter.clone()))
+                .wrap(ApiKeyAuth::new(config, limiter))
+                .route("/api/regions", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let guess = |key: &str| {
+            test::TestRequest::get()
+                .uri("/api/regions")
+                .peer_addr("10.0.0.1:1234".parse().unwrap())
+                .insert_header((API_KEY_HEADER, key.to_string()))
+                .to_request()
+        };
+        for key in ["a", "b", "c"] {
+            let resp = test::call_service(&app, guess(key)).await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(error_code(&resp), Some("invalid_api_key"));
+        }
+        let resp = test::call_service(&app, guess("d")).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(error_code(&resp), Some("rate_limited"));
+        assert_eq!(resp.headers().get("retry-after").unwrap(), "1");
+
+        // Ni siquiera la llave buena pasa desde esa IP hasta que se recargue
+        assert_eq!(test::call_service(&app, guess("secreta")).await.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -0,0 +1,253 @@
+// src/middlewares/encoding.rs
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::Error;
+use futures::future::LocalBoxFuture;
+use log::warn;
+use serde_json::Value;
+use std::future::{ready, Ready};
+
+/// Formato en que se escriben las respuestas JSON según el `Accept` de la petición.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseEncoding {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl ResponseEncoding {
+    /// Formato y precedencia del tipo: los comodines quedan por debajo de un tipo
+    /// explícito con el mismo `q`.
+    fn from_media_type(media_type: &str) -> Option<(Self, u8)> {
+        match media_type {
+            "application/json" => Some((Self::Json, 2)),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some((Self::MessagePack, 2)),
+            "application/cbor" => Some((Self::Cbor, 2)),
+            "application/*" => Some((Self::Json, 1)),
+            "*/*" => Some((Self::Json, 0)),
+            _ => None,
+        }
+    }
+
+    /// El formato aceptado con mayor `q`; ante empate gana el tipo explícito sobre
+    /// los comodines y, entre iguales, el primero. Sin `Accept` o sin ningún tipo
+    /// conocido se responde JSON.
+    pub fn negotiate(accept: Option<&str>) -> Self {
+        let mut best: Option<(f32, u8, Self)> = None;
+        for item in accept.unwrap_or_default().split(',') {
+            let mut parts = item.split(';').map(str::trim);
+            let Some((encoding, precedence)) =
+                parts.next().and_then(|media| Self::from_media_type(&media.to_ascii_lowercase()))
+            else {
+                continue;
+            };
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let better = best.is_none_or(|(current, current_precedence, _)| {
+                quality > current || (quality == current && precedence > current_precedence)
+            });
+            if quality > 0.0 && better {
+                best = Some((quality, precedence, encoding));
+            }
+        }
+        best.map_or(Self::Json, |(_, _, encoding)| encoding)
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::MessagePack => "application/msgpack",
+            Self::Cbor => "application/cbor",
+        }
+    }
+
+    /// Reescribe un cuerpo JSON; `None` si no es JSON válido o no se pudo codificar.
+    fn encode(self, json: &[u8]) -> Option<Vec<u8>> {
+        let value: Value = serde_json::from_slice(json).ok()?;
+        let encoded = match self {
+            Self::Json => return None,
+            Self::MessagePack => rmp_serde::to_vec(&value).map_err(|e| e.to_string()),
+            Self::Cbor => {
+                let mut buffer = Vec::new();
+                ciborium::into_writer(&value, &mut buffer).map(|_| buffer).map_err(|e| e.to_string())
+            }
+        };
+        encoded.map_err(|e| warn!("Could not encode response as {}: {}", self.content_type(), e)).ok()
+    }
+}
+
+fn is_json(response: &actix_web::HttpResponse<impl MessageBody>) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|media| media.trim().to_ascii_lowercase())
+        .is_some_and(|media| {
+            media == "application/json" || (media.starts_with("application/") && media.ends_with("+json"))
+        })
+}
+
+/// Convierte las respuestas `application/json` y `application/*+json` (GeoJSON) a
+/// MessagePack o CBOR cuando el cliente lo pide en `Accept`, con el mismo esquema.
+/// Los demás tipos (NDJSON, teselas, HTML) pasan sin cambios.
+pub struct ContentNegotiation;
+
+impl<S, B> Transform<S, ServiceRequest> for ContentNegotiation
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = ContentNegotiationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ContentNegotiationMiddleware { service }))
+    }
+}
+
+pub struct ContentNegotiationMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for ContentNegotiationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let encoding = ResponseEncoding::negotiate(
+            req.headers().get(header::ACCEPT).and_then(|value| value.to_str().ok()),
+        );
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if !is_json(res.response()) {
+                return Ok(res.map_into_boxed_body());
+            }
+            // La representación depende de `Accept`, también para los cachés intermedios
+            res.headers_mut().append(header::VARY, HeaderValue::from_static("Accept"));
+            if encoding == ResponseEncoding::Json {
+                return Ok(res.map_into_boxed_body());
+            }
+
+            let (req, res) = res.into_parts();
+            let (mut res, body) = res.into_parts();
+            let json = body::to_bytes(body)
+                .await
+                .map_err(|e| actix_web::error::ErrorInternalServerError(e.into().to_string()))?;
+
+            let res = match encoding.encode(&json) {
+                Some(encoded) => {
+                    res.headers_mut()
+                        .insert(header::CONTENT_TYPE, HeaderValue::from_static(encoding.content_type()));
+                    res.set_body(BoxBody::new(encoded))
+                }
+                None => res.set_body(BoxBody::new(json)),
+            };
+            Ok(ServiceResponse::new(req, res))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test as actix_test, web, App, HttpResponse};
+    use serde_json::json;
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(ResponseEncoding::negotiate(None), ResponseEncoding::Json);
+        assert_eq!(ResponseEncoding::negotiate(Some("application/msgpack")), ResponseEncoding::MessagePack);
+        assert_eq!(
+            ResponseEncoding::negotiate(Some("application/json;q=0.5, application/cbor")),
+            ResponseEncoding::Cbor
+        );
+        assert_eq!(
+            ResponseEncoding::negotiate(Some("application/msgpack;q=0, text/html")),
+            ResponseEncoding::Json
+        );
+        // Un comodín no le gana a un tipo explícito con el mismo `q`
+        assert_eq!(
+            ResponseEncoding::negotiate(Some("*/*, application/msgpack")),
+            ResponseEncoding::MessagePack
+        );
+        assert_eq!(
+            ResponseEncoding::negotiate(Some("application/*, application/cbor")),
+            ResponseEncoding::Cbor
+        );
+        assert_eq!(
+            ResponseEncoding::negotiate(Some("*/*, application/msgpack;q=0.5")),
+            ResponseEncoding::Json
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_encodes_json_responses() {
+        let payload = json!({ "success": true, "routes": [{ "code": "A", "fees": 0.25 }], "region": null });
+        let expected = payload.clone();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(ContentNegotiation)
+                .route("/", web::get().to(move || {
+                    let payload = payload.clone();
+                    async move { HttpResponse::Ok().json(payload) }
+                }))
+                .route("/geojson", web::get().to(|| async {
+                    HttpResponse::Ok()
+                        .content_type("application/geo+json")
+                        .body(r#"{"type":"FeatureCollection","features":[]}"#)
+                }))
+                .route("/text", web::get().to(|| async { HttpResponse::Ok().body("ok") })),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/")
+            .insert_header((header::ACCEPT, "application/msgpack"))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/msgpack");
+        let decoded: Value = rmp_serde::from_slice(&actix_test::read_body(resp).await).unwrap();
+        assert_eq!(decoded, expected);
+
+        let req = actix_test::TestRequest::get()
+            .uri("/")
+            .insert_header((header::ACCEPT, "application/cbor"))
+            .to_request();
+        let decoded: Value = ciborium::from_reader(&actix_test::call_and_read_body(&app, req).await[..]).unwrap();
+        assert_eq!(decoded, expected);
+
+        let req = actix_test::TestRequest::get()
+            .uri("/geojson")
+            .insert_header((header::ACCEPT, "application/msgpack"))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/msgpack");
+        let decoded: Value = rmp_serde::from_slice(&actix_test::read_body(resp).await).unwrap();
+        assert_eq!(decoded, json!({ "type": "FeatureCollection", "features": [] }));
+
+        // Lo que no es JSON no se toca
+        let req = actix_test::TestRequest::get()
+            .uri("/text")
+            .insert_header((header::ACCEPT, "application/msgpack"))
+            .to_request();
+        assert_eq!(actix_test::call_and_read_body(&app, req).await, "ok");
+    }
+}
@@ -1,11 +1,31 @@
 // src/middleware/logger.rs
 use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::Error;
 use futures::future::LocalBoxFuture;
-use log::info;
+use geo_reader::metrics::{HTTP_REQUESTS, HTTP_REQUEST_DURATION};
 use std::future::{ready, Ready};
 use std::time::Instant;
+use tracing::{field, info, info_span, Instrument};
 
+use crate::api::error::ApiError;
+
+/// Encabezado con el identificador de la petición; se respeta el que envíe el cliente.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Usa el `X-Request-Id` entrante si es razonable o genera uno nuevo.
+fn request_id(req: &ServiceRequest) -> String {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// Abre un span `request` con el id de la petición y escribe una línea de acceso al
+/// terminar. Los handlers pueden completar los campos `region` y `plans` del span con
+/// `tracing::Span::current().record(...)`.
 pub struct RequestLogger;
 
 impl<S, B> Transform<S, ServiceRequest> for RequestLogger
@@ -44,25 +64,85 @@ where
     fn call(&self, req: ServiceRequest) -> Self::Future {
         let start = Instant::now();
         let method = req.method().clone();
-        let uri = req.uri().clone();
-
-        let fut = self.service.call(req);
-
-        Box::pin(async move {
-            let res = fut.await?;
-            let duration = start.elapsed();
-            let status = res.status();
-
-            // Formato similar a Morgan
-            info!(
-                "{} {} {} {}ms",
-                method,
-                uri,
-                status.as_u16(),
-                duration.as_millis()
-            );
-
-            Ok(res)
-        })
+        let path = req.path().to_string();
+        let client_ip = req.connection_info().realip_remote_addr().unwrap_or("unknown").to_string();
+        let request_id = request_id(&req);
+
+        let span = info_span!(
+            "request",
+            request_id = %request_id,
+            region = field::Empty,
+            plans = field::Empty,
+        );
+        let fut = span.in_scope(|| self.service.call(req));
+
+        Box::pin(
+            async move {
+                let mut res = fut.await?;
+                let duration = start.elapsed();
+                let status = res.status();
+
+                // Se etiqueta con el patrón de la ruta (no la URI) para no crear una serie por consulta
+                let route = res.request().match_pattern().unwrap_or_else(|| "unmatched".to_string());
+                let labels = [route.as_str(), method.as_str(), status.as_str()];
+                HTTP_REQUESTS.with_label_values(&labels).inc();
+                HTTP_REQUEST_DURATION.with_label_values(&labels).observe(duration.as_secs_f64());
+
+                let code = res
+                    .response()
+                    .error()
+                    .and_then(|e| e.as_error::<ApiError>())
+                    .map(ApiError::code);
+
+                info!(
+                    target: "access",
+                    method = %method,
+                    path = %path,
+                    status = status.as_u16(),
+                    latency_ms = duration.as_secs_f64() * 1000.0,
+                    client_ip = %client_ip,
+                    code,
+                    "{} {} {} {}ms",
+                    method,
+                    path,
+                    status.as_u16(),
+                    duration.as_millis()
+                );
+
+                if let Ok(value) = HeaderValue::from_str(&request_id) {
+                    res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+                }
+                Ok(res)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn test_request_id_header() {
+        let app = test::init_service(
+            App::new()
+                .wrap(RequestLogger)
+                .route("/health", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/health").to_request();
+        let resp = test::call_service(&app, req).await;
+        let generated = resp.headers().get(REQUEST_ID_HEADER).unwrap().to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(generated).is_ok());
+
+        let req = test::TestRequest::get()
+            .uri("/health")
+            .insert_header((REQUEST_ID_HEADER, "abc-123"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.headers().get(REQUEST_ID_HEADER).unwrap(), "abc-123");
     }
 }
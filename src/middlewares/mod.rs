@@ -1 +1,4 @@
-pub mod logger;
\ No newline at end of file
+pub mod api_key;
+pub mod encoding;
+pub mod logger;
+pub mod rate_limit;
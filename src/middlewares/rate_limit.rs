@@ -0,0 +1,252 @@
+// src/middlewares/rate_limit.rs
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use actix_web::{Error, HttpMessage};
+use futures::future::LocalBoxFuture;
+use log::warn;
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use super::api_key::{Caller, Quota};
+use crate::api::error::ApiError;
+
+/// Cada cuántas peticiones se descartan los baldes llenos.
+const PRUNE_EVERY: u64 = 1024;
+
+/// Balde de tokens: se recarga a `requests_per_minute / 60` tokens por segundo hasta `burst`.
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Resultado de consumir un token.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub burst: u32,
+    pub remaining: u32,
+    /// Segundos hasta que el balde vuelva a estar lleno
+    pub reset: u64,
+    /// Segundos hasta el próximo token disponible (solo si se rechazó)
+    pub retry_after: u64,
+}
+
+impl TokenBucket {
+    fn refill(&mut self, quota: Quota, now: Instant) {
+        let rate = quota.requests_per_minute as f64 / 60.0;
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(quota.burst() as f64);
+        self.updated = now;
+    }
+
+    /// Decisión para el estado actual del balde, sin consumir nada.
+    fn decision(&self, quota: Quota, allowed: bool) -> RateLimitDecision {
+        let rate = (quota.requests_per_minute as f64 / 60.0).max(f64::EPSILON);
+        RateLimitDecision {
+            allowed,
+            limit: quota.requests_per_minute,
+            burst: quota.burst(),
+            remaining: self.tokens.floor() as u32,
+            reset: ((quota.burst() as f64 - self.tokens) / rate).ceil() as u64,
+            retry_after: if allowed { 0 } else { ((1.0 - self.tokens) / rate).ceil() as u64 },
+        }
+    }
+}
+
+/// Baldes por cliente, compartidos entre los workers del servidor.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    buckets: Arc<Mutex<HashMap<String, (Quota, TokenBucket)>>>,
+    calls: Arc<AtomicU64>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cobra un token en cada balde solo si todos tienen uno disponible; si alguno
+    /// está vacío no se consume nada. Devuelve la decisión de cada balde, en orden.
+    pub fn check_all(&self, limits: &[(String, Quota)]) -> Vec<RateLimitDecision> {
+        self.check_all_at(limits, Instant::now())
+    }
+
+    fn check_all_at(&self, limits: &[(String, Quota)], now: Instant) -> Vec<RateLimitDecision> {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        let calls = self.calls.fetch_add(1, Ordering::Relaxed);
+        if calls % PRUNE_EVERY == PRUNE_EVERY - 1 {
+            // Un balde lleno equivale a uno nuevo, así que se puede descartar
+            buckets.retain(|_, (quota, bucket)| {
+                bucket.refill(*quota, now);
+                bucket.tokens < quota.burst() as f64
+            });
+        }
+
+        for (name, quota) in limits {
+            let (stored_quota, bucket) = buckets.entry(name.clone()).or_insert_with(|| {
+                (*quota, TokenBucket { tokens: quota.burst() as f64, updated: now })
+            });
+            *stored_quota = *quota;
+            bucket.refill(*quota, now);
+        }
+
+        let allowed = limits.iter().all(|(name, _)| buckets[name].1.tokens >= 1.0);
+        limits
+            .iter()
+            .map(|(name, quota)| {
+                let (_, bucket) = buckets.get_mut(name).unwrap();
+                if allowed {
+                    bucket.tokens -= 1.0;
+                }
+                bucket.decision(*quota, bucket.tokens >= 1.0 || allowed)
+            })
+            .collect()
+    }
+}
+
+fn insert_headers(headers: &mut HeaderMap, decision: &RateLimitDecision) {
+    let values = [
+        ("ratelimit-limit", decision.limit.to_string()),
+        ("ratelimit-remaining", decision.remaining.to_string()),
+        ("ratelimit-reset", decision.reset.to_string()),
+        ("ratelimit-policy", format!("{};w=60;burst={}", decision.limit, decision.burst)),
+    ];
+    for (name, value) in values {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(HeaderName::from_static(name), value);
+        }
+    }
+}
+
+/// Respuesta `429` con los encabezados del balde que se agotó.
+pub(super) fn rate_limited(req: ServiceRequest, bucket: &str, decision: &RateLimitDecision) -> ServiceResponse {
+    warn!("Rate limit exceeded for {}", bucket);
+    // Con el error en la respuesta, `RequestLogger` registra su `code`
+    let mut response = req.error_response(ApiError::RateLimited);
+    insert_headers(response.headers_mut(), decision);
+    response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(decision.retry_after));
+    response
+}
+
+/// Aplica la cuota del [`Caller`] que dejó `ApiKeyAuth`; sin cuota la petición pasa.
+pub struct RateLimit {
+    limiter: RateLimiter,
+}
+
+impl RateLimit {
+    pub fn new(limiter: RateLimiter) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service,
+            limiter: self.limiter.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: S,
+    limiter: RateLimiter,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let caller = req.extensions().get::<Caller>().cloned();
+        // Se cobra en todos los baldes del cliente o en ninguno: basta con que uno
+        // esté vacío para rechazar. Los encabezados son los del balde que rechazó o,
+        // si pasó, los del propio cliente.
+        let buckets = caller.as_ref().map(Caller::buckets).unwrap_or_default();
+        let decisions: Vec<(&String, RateLimitDecision)> = buckets
+            .iter()
+            .map(|(bucket, _)| bucket)
+            .zip(self.limiter.check_all(&buckets))
+            .collect();
+        let rejected = decisions.iter().find(|(_, decision)| !decision.allowed);
+
+        match rejected {
+            Some((bucket, decision)) => {
+                let response = rate_limited(req, bucket, decision).map_into_right_body();
+                Box::pin(async move { Ok(response) })
+            }
+            None => {
+                let decision = decisions.first().map(|(_, decision)| *decision);
+                let fut = self.service.call(req);
+                Box::pin(async move {
+                    let mut res = fut.await?;
+                    if let Some(decision) = decision {
+                        insert_headers(res.headers_mut(), &decision);
+                    }
+                    Ok(res.map_into_left_body())
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    impl RateLimiter {
+        fn check_at(&self, bucket: &str, quota: Quota, now: Instant) -> RateLimitDecision {
+            self.check_all_at(&[(bucket.to_string(), quota)], now)[0]
+        }
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::new();
+        let quota = Quota { requests_per_minute: 60, burst: Some(2) };
+        let start = Instant::now();
+
+        assert!(limiter.check_at("ip:1", quota, start).allowed);
+        let second = limiter.check_at("ip:1", quota, start);
+        assert!(second.allowed);
+        assert_eq!(second.remaining, 0);
+
+        let rejected = limiter.check_at("ip:1", quota, start);
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.retry_after, 1);
+
+        // Otro cliente tiene su propio balde
+        assert!(limiter.check_at("ip:2", quota, start).allowed);
+
+        // Un token por segundo
+        assert!(limiter.check_at("ip:1", quota, start + Duration::from_secs(1)).allowed);
+        assert!(!limiter.check_at("ip:1", quota, start + Duration::from_secs(1)).allowed);
+    }
+}
@@ -1,20 +1,24 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::time::SystemTime;
+use utoipa::ToSchema;
 
 /// Representa una ruta completa en el sistema.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct Route {
     pub route_id: i32,              // ID único de la ruta
     pub bus_id: i32,                // ID del bus asociado
     pub direction_id: Option<i32>,  // Dirección (si aplica)
+    #[schema(value_type = Object)]
     pub route_geometry: Value,      // Geometría de la ruta en formato GeoJSON
     pub distance: Option<f64>,      // Distancia total, si es relevante
     pub number_route: String,       // Número de la ruta (por ejemplo, "48")
     pub code_route: String,         // Código interno de la ruta
     pub fees: Option<f64>,          // Tarifa estándar
     pub special_fees: Option<f64>,  // Tarifas especiales (si aplica)
+    #[schema(value_type = Option<Object>)]
     pub first_trip: Option<SystemTime>, // Hora del primer viaje
+    #[schema(value_type = Option<Object>)]
     pub last_trip: Option<SystemTime>,  // Hora del último viaje
     pub frequency: Option<String>,  // Frecuencia en minutos u otra representación
     pub photo_url: Option<String>,  // URL de la foto asociada
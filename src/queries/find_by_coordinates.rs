@@ -1,13 +1,12 @@
-use crate::db::connect_to_db;
+use deadpool_postgres::{Client, GenericClient};
 use tokio_postgres::Error;
 
 /// Find a place by exact coordinates.
 pub async fn find_by_coordinates(
     lat: f64, 
-    lon: f64
+    lon: f64,
+    client: &Client,
 ) -> Result<Vec<String>, Error> {
-    let client = connect_to_db().await?;
-
     let query = "
         SELECT name 
         FROM planet_osm_point 
@@ -15,7 +14,8 @@ pub async fn find_by_coordinates(
         LIMIT 1;
     ";
 
-    let rows = client.query(query, &[&lon, &lat]).await?;
+    let statement = client.prepare_cached(query).await?;
+    let rows = client.query(&statement, &[&lon, &lat]).await?;
     let places: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
 
     Ok(places)
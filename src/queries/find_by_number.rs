@@ -1,5 +1,6 @@
 use serde_json::Value;
-use tokio_postgres::{Client, Error};
+use deadpool_postgres::{Client, GenericClient};
+use tokio_postgres::Error;
 use crate::queries::_structs::Route;
 
 pub async fn get_routes_by_number(
@@ -30,7 +31,8 @@ pub async fn get_routes_by_number(
         LIMIT 10;
     ";
 
-    let rows = client.query(query, &[&number_route]).await?;
+    let statement = client.prepare_cached(query).await?;
+    let rows = client.query(&statement, &[&number_route]).await?;
 
     let routes: Vec<Route> = rows
         .iter()
@@ -1,4 +1,5 @@
-use tokio_postgres::{Client, Error};
+use deadpool_postgres::{Client, GenericClient};
+use tokio_postgres::Error;
 
 /// Consulta lugares por nombre y devuelve un array de resultados.
 pub async fn find_places_by_name(
@@ -17,7 +18,8 @@ pub async fn find_places_by_name(
         LIMIT 10;
     ";
 
-    let rows = client.query(query, &[&format!("%{}%", name)]).await?;
+    let statement = client.prepare_cached(query).await?;
+    let rows = client.query(&statement, &[&format!("%{}%", name)]).await?;
     
     // Mapeamos los resultados en un vector de tuplas (nombre, longitud, latitud).
     let places: Vec<(String, f64, f64)> = rows.iter()
@@ -1,4 +1,5 @@
-use tokio_postgres::{Client, Error};
+use deadpool_postgres::{Client, GenericClient};
+use tokio_postgres::Error;
 use crate::queries::_structs::Route;
 use serde_json::Value;
 
@@ -70,7 +71,8 @@ pub async fn find_route(
         LIMIT 10;
     ";
 
-    let rows = client.query(query, &[&start_lng, &start_lat, &end_lng, &end_lat]).await?;
+    let statement = client.prepare_cached(query).await?;
+    let rows = client.query(&statement, &[&start_lng, &start_lat, &end_lng, &end_lat]).await?;
 
     let routes: Vec<Route> = rows
     .iter()
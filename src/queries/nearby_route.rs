@@ -1,4 +1,5 @@
-use tokio_postgres::{Client, Error};
+use deadpool_postgres::{Client, GenericClient};
+use tokio_postgres::Error;
 use crate::queries::_structs::Route;
 use serde_json::Value;
 
@@ -37,7 +38,8 @@ pub async fn get_nearby_routes(
         LIMIT 10;
     ";
 
-    let rows = client.query(query, &[&longitude, &latitude, &max_distance]).await?;
+    let statement = client.prepare_cached(query).await?;
+    let rows = client.query(&statement, &[&longitude, &latitude, &max_distance]).await?;
 
     let routes: Vec<Route> = rows
         .iter()
@@ -1,8 +1,14 @@
-use geo::Coord;
 use serde::{Deserialize, Serialize};
-use geo_types::{Point, MultiPolygon, Polygon, LineString};
-use serde::de::{self, Deserializer, Visitor, SeqAccess};
-use std::fmt;
+use geo::EuclideanDistance;
+use geo_types::Point;
+
+/// Índice del vértice de `part` más cercano al punto y su distancia en grados.
+fn closest_in_part(part: &[Vec<f64>], point: Point<f64>) -> Option<(usize, f64)> {
+    part.iter()
+        .enumerate()
+        .map(|(idx, coord)| (idx, Point::new(coord[0], coord[1]).euclidean_distance(&point)))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct GeoJsonFeatureCollection<T> {
@@ -12,6 +18,23 @@ pub struct GeoJsonFeatureCollection<T> {
     pub features: Vec<GeoJsonFeature<T>>,
 }
 
+impl<T> GeoJsonFeatureCollection<T> {
+    /// Colección en WGS84 (CRS84), como las capas de datos originales.
+    pub fn new(name: &str, features: Vec<GeoJsonFeature<T>>) -> Self {
+        Self {
+            r#type: "FeatureCollection".to_string(),
+            name: name.to_string(),
+            crs: GeoJsonCrs {
+                r#type: "name".to_string(),
+                properties: GeoJsonCrsProperties {
+                    name: "urn:ogc:def:crs:OGC:1.3:CRS84".to_string(),
+                },
+            },
+            features,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct GeoJsonCrs {
     pub r#type: String,
@@ -40,6 +63,56 @@ pub enum GeoJsonGeometry {
     MultiLineString { coordinates: Vec<Vec<Vec<f64>>> },
 }
 
+impl GeoJsonGeometry {
+    /// Tramos de una geometría lineal (`LineString` o `MultiLineString`); vacío para las demás.
+    pub fn line_parts(&self) -> Vec<&[Vec<f64>]> {
+        match self {
+            GeoJsonGeometry::LineString { coordinates } => vec![coordinates.as_slice()],
+            GeoJsonGeometry::MultiLineString { coordinates } => {
+                coordinates.iter().map(|part| part.as_slice()).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Todos los vértices de los tramos, en orden.
+    pub fn line_coords(&self) -> impl Iterator<Item = &Vec<f64>> {
+        self.line_parts().into_iter().flatten()
+    }
+
+    /// Vértice más cercano al punto.
+    pub fn closest_vertex(&self, point: Point<f64>) -> Option<Point<f64>> {
+        self.line_parts()
+            .into_iter()
+            .filter_map(|part| closest_in_part(part, point).map(|(idx, distance)| (&part[idx], distance)))
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(coord, _)| Point::new(coord[0], coord[1]))
+    }
+
+    /// Vértices recorridos entre los más cercanos a `from` y a `to`, en ese orden, dentro
+    /// del tramo que mejor contiene a ambos puntos.
+    pub fn slice_between(&self, from: Point<f64>, to: Point<f64>) -> Option<Vec<Vec<f64>>> {
+        self.line_parts()
+            .into_iter()
+            .filter_map(|part| {
+                let (start, start_offset) = closest_in_part(part, from)?;
+                let (end, end_offset) = closest_in_part(part, to)?;
+                let coords = if start <= end {
+                    part[start..=end].to_vec()
+                } else {
+                    part[end..=start].iter().rev().cloned().collect()
+                };
+                Some((coords, start_offset + end_offset))
+            })
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(coords, _)| coords)
+    }
+
+    pub fn is_linear(&self) -> bool {
+        matches!(self, GeoJsonGeometry::LineString { .. } | GeoJsonGeometry::MultiLineString { .. })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct DepartmentProperties {
     #[serde(rename = "FCODE")]
@@ -63,6 +136,32 @@ pub struct DepartmentProperties {
 }
 pub type DepartmentFeatureCollection = GeoJsonFeatureCollection<DepartmentProperties>;
 
+/// Límites municipales (distritos). `NA2` identifica el departamento al que pertenecen.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MunicipalityProperties {
+    #[serde(rename = "FCODE")]
+    pub fcode: Option<String>,
+    #[serde(rename = "COD")]
+    pub cod: Option<i32>,
+    #[serde(rename = "NA2")]
+    pub na2: Option<String>,
+    #[serde(rename = "NA3")]
+    pub na3: Option<String>,
+    #[serde(rename = "NAM")]
+    pub nam: Option<String>,
+    #[serde(rename = "AREA_KM")]
+    pub area_km: Option<f64>,
+}
+
+impl MunicipalityProperties {
+    /// Nombre del municipio/distrito: `NA3` si existe, si no `NAM`.
+    pub fn name(&self) -> Option<&str> {
+        self.na3.as_deref().or(self.nam.as_deref())
+    }
+}
+
+pub type MunicipalityFeatureCollection = GeoJsonFeatureCollection<MunicipalityProperties>;
+
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct BusStopProperties {
@@ -111,6 +210,11 @@ pub struct RouteProperties {
     pub cantidad_d: Option<i32>,
     #[serde(rename = "Shape_Leng")]
     pub shape_leng: Option<f64>,
+    // Solo vienen de la tabla `buses`; los GeoJSON no las traen.
+    #[serde(default)]
+    pub fees: Option<f64>,
+    #[serde(default)]
+    pub frequency: Option<String>,
 }
 
 pub type RouteFeatureCollection = GeoJsonFeatureCollection<RouteProperties>;
@@ -155,15 +259,16 @@ pub struct TransferPoint {
     pub to_route: String,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct RouteSegment {
+    pub variant_id: String, // ver `spatial_search::route_variant_ids`
     pub route: RouteProperties,
     pub transfer_point: TransferPoint,
     pub transfer_type: TransferType,
     pub segment_distance: f64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct RoutePlan {
     pub routes: Vec<RouteSegment>,
     pub total_distance: f64,
@@ -211,9 +316,20 @@ impl RoutePlan {
         }
     }
 
+    /// Códigos públicos de las rutas del plan, sin distinguir variantes.
+    pub fn route_codes(&self) -> Vec<Option<String>> {
+        self.routes.iter().map(|segment| segment.route.codigo_de.clone()).collect()
+    }
+
     pub fn add_segment(&mut self, segment: RouteSegment) {
         self.total_distance += segment.segment_distance;
         self.routes.push(segment);
         self.transfers_count = self.routes.len() as i32 - 1;
     }
-}
\ No newline at end of file
+}
+
+impl Default for RoutePlan {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,19 +1,29 @@
-use geo_types::{MultiPolygon, Polygon, Coord};
-use serde::de::DeserializeOwned;
-use log::{debug, info, error};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Serialize;
+use serde_json::value::RawValue;
+use serde_json::{Map, Value};
+use log::{info, warn, error};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Read};
+use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::time::Instant;
 
 
-use super::_structs::{BusStopFeatureCollection, BusStopProperties, DepartmentFeatureCollection, GeoJsonCrs, GeoJsonCrsProperties, GeoJsonFeature, RouteFeatureCollection, RouteProperties};
+use super::_structs::{BusStopFeatureCollection, BusStopProperties, DepartmentFeatureCollection, GeoJsonFeature, GeoJsonFeatureCollection, MunicipalityFeatureCollection, RouteFeatureCollection, RouteProperties};
+use super::data_source::PlannerDataSource;
+use super::manifest::{rename_properties, DataManifest, LayerConfig, LayerKind};
 
 pub struct DataLoader {
     data_dir: PathBuf,
+    manifest: Option<DataManifest>,
     departments: DepartmentFeatureCollection,
+    municipalities: MunicipalityFeatureCollection,
     bus_stops: BusStopFeatureCollection,
     routes: RouteFeatureCollection,
+    summary: LoadSummary,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -22,125 +32,313 @@ pub enum LoaderError {
     Io(#[from] std::io::Error),
     #[error("JSON parsing error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("Invalid data manifest: {0}")]
+    Manifest(#[from] toml::de::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] tokio_postgres::Error),
     #[error("GeoJSON parsing error: {0}")]
     GeoJson(String),
     #[error("Invalid data: {0}")]
     InvalidData(String),
 }
 
+/// Feature descartada durante la carga.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureError {
+    pub file: String,
+    pub index: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FileLoadSummary {
+    pub file: String,
+    pub loaded: usize,
+    pub skipped: usize,
+    pub elapsed_ms: u128,
+}
+
+/// Resumen de la última llamada a [`DataLoader::load_all`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LoadSummary {
+    pub files: Vec<FileLoadSummary>,
+    pub skipped_features: Vec<FeatureError>,
+    pub elapsed_ms: u128,
+}
+
+impl LoadSummary {
+    pub fn total_loaded(&self) -> usize {
+        self.files.iter().map(|file| file.loaded).sum()
+    }
+
+    pub fn total_skipped(&self) -> usize {
+        self.files.iter().map(|file| file.skipped).sum()
+    }
+}
+
+/// Lee un FeatureCollection feature por feature sin construir el documento completo
+/// en memoria. Las features inválidas se descartan y se reportan en `skipped`.
+/// `properties` renombra propiedades según el manifiesto antes de tiparlas.
+pub fn read_feature_collection<T, R>(
+    reader: R,
+    file: &str,
+    properties: &BTreeMap<String, String>,
+    skipped: &mut Vec<FeatureError>,
+) -> Result<GeoJsonFeatureCollection<T>, LoaderError>
+where
+    T: DeserializeOwned,
+    R: Read,
+{
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let collection = CollectionSeed { file, properties, skipped, marker: PhantomData }
+        .deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(collection)
+}
+
+struct CollectionSeed<'a, T> {
+    file: &'a str,
+    properties: &'a BTreeMap<String, String>,
+    skipped: &'a mut Vec<FeatureError>,
+    marker: PhantomData<T>,
+}
+
+impl<'de, T: DeserializeOwned> DeserializeSeed<'de> for CollectionSeed<'_, T> {
+    type Value = GeoJsonFeatureCollection<T>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Visitor<'de> for CollectionSeed<'_, T> {
+    type Value = GeoJsonFeatureCollection<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a GeoJSON FeatureCollection")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut collection = GeoJsonFeatureCollection::new("", Vec::new());
+        let mut has_type = false;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "type" => {
+                    collection.r#type = map.next_value()?;
+                    has_type = true;
+                }
+                "name" => collection.name = map.next_value()?,
+                "crs" => collection.crs = map.next_value()?,
+                "features" => {
+                    collection.features = map.next_value_seed(FeaturesSeed {
+                        file: self.file,
+                        properties: self.properties,
+                        skipped: &mut *self.skipped,
+                        marker: PhantomData,
+                    })?;
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        if !has_type {
+            return Err(de::Error::custom("Invalid GeoJSON structure: missing \"type\""));
+        }
+
+        Ok(collection)
+    }
+}
+
+struct FeaturesSeed<'a, T> {
+    file: &'a str,
+    properties: &'a BTreeMap<String, String>,
+    skipped: &'a mut Vec<FeatureError>,
+    marker: PhantomData<T>,
+}
+
+impl<'de, T: DeserializeOwned> DeserializeSeed<'de> for FeaturesSeed<'_, T> {
+    type Value = Vec<GeoJsonFeature<T>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Visitor<'de> for FeaturesSeed<'_, T> {
+    type Value = Vec<GeoJsonFeature<T>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array of GeoJSON features")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut features = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        let mut index = 0;
+
+        // Cada feature se lee como texto crudo y luego se tipa, así un error
+        // de esquema solo descarta esa feature.
+        while let Some(raw) = seq.next_element::<Box<RawValue>>()? {
+            match parse_feature::<T>(raw.get(), self.properties) {
+                Ok(feature) => features.push(feature),
+                Err(e) => {
+                    warn!("Skipping feature {} in {}: {}", index, self.file, e);
+                    self.skipped.push(FeatureError {
+                        file: self.file.to_string(),
+                        index,
+                        reason: e.to_string(),
+                    });
+                }
+            }
+            index += 1;
+        }
+
+        Ok(features)
+    }
+}
+
+fn parse_feature<T: DeserializeOwned>(
+    raw: &str,
+    properties: &BTreeMap<String, String>,
+) -> Result<GeoJsonFeature<T>, serde_json::Error> {
+    if properties.is_empty() {
+        return serde_json::from_str(raw);
+    }
+
+    let mut feature: GeoJsonFeature<Map<String, Value>> = serde_json::from_str(raw)?;
+    rename_properties(&mut feature.properties, properties);
+
+    Ok(GeoJsonFeature {
+        r#type: feature.r#type,
+        properties: serde_json::from_value(Value::Object(feature.properties))?,
+        geometry: feature.geometry,
+    })
+}
+
 impl DataLoader {
+    /// Usa `<data_dir>/manifest.toml` si existe, o la lista histórica de archivos.
     pub fn new<P: AsRef<Path>>(data_dir: P) -> Self {
         Self {
             data_dir: data_dir.as_ref().to_path_buf(),
-            departments: DepartmentFeatureCollection {
-                r#type: String::new(),
-                name: String::new(),
-                crs: GeoJsonCrs {
-                    r#type: String::new(),
-                    properties: GeoJsonCrsProperties {
-                        name: String::new(),
-                    },
-                },
-                features: Vec::new(),
-            },
-            bus_stops: BusStopFeatureCollection {
-                r#type: String::new(),
-                name: String::new(),
-                crs: GeoJsonCrs {
-                    r#type: String::new(),
-                    properties: GeoJsonCrsProperties {
-                        name: String::new(),
-                    },
-                },
-                features: Vec::new(),
-            },
-            routes: RouteFeatureCollection {
-                r#type: String::new(),
-                name: String::new(),
-                crs: GeoJsonCrs {
-                    r#type: String::new(),
-                    properties: GeoJsonCrsProperties {
-                        name: String::new(),
-                    },
-                },
-                features: Vec::new(),
-            },
+            manifest: None,
+            departments: GeoJsonFeatureCollection::new("", Vec::new()),
+            municipalities: GeoJsonFeatureCollection::new("", Vec::new()),
+            bus_stops: GeoJsonFeatureCollection::new("", Vec::new()),
+            routes: GeoJsonFeatureCollection::new("", Vec::new()),
+            summary: LoadSummary::default(),
         }
     }
 
-    /// Carga todos los datos necesarios
+    pub fn with_manifest(mut self, manifest: DataManifest) -> Self {
+        self.manifest = Some(manifest);
+        self
+    }
+
+    /// Carga todas las capas del manifiesto
     pub fn load_all(&mut self) -> Result<(), LoaderError> {
-        self.departments = self.load_geojson("LIM DEPARTAMENTALES.geojson")?;
-        self.bus_stops = self.load_geojson("Paradas Transporte Colectivo AMSS.geojson")?;
-    
-        let route_files = [
-            "Rutas Interdepartamentales.geojson",
-            "Rutas Interurbanas.geojson",
-            "Rutas Urbanas.geojson",
-        ];
-    
-        self.routes = route_files
-        .iter()
-        .map(|&file| -> Result<RouteFeatureCollection, LoaderError> {
-            self.load_geojson(file)
-        })
-        .try_fold(
-            RouteFeatureCollection {
-                r#type: String::new(),
-                name: String::new(),
-                crs: GeoJsonCrs {
-                    r#type: String::new(),
-                    properties: GeoJsonCrsProperties {
-                        name: String::new(),
-                    },
-                },
-                features: Vec::new(),
-            },
-            |mut acc, collection| -> Result<RouteFeatureCollection, LoaderError> {
-                let collection = collection?;
-                acc.features.extend(collection.features);
-                Ok(acc)
-            },
-        )?;
+        let started = Instant::now();
+        self.summary = LoadSummary::default();
+
+        let manifest = self.manifest()?;
+
+        self.departments = self.load_layers(&manifest, LayerKind::Departments)?;
+        self.municipalities = self.load_layers(&manifest, LayerKind::Municipalities)?;
+        self.bus_stops = self.load_layers(&manifest, LayerKind::Stops)?;
+        self.routes = self.load_layers(&manifest, LayerKind::Routes)?;
+
+        if self.municipalities.features.is_empty() {
+            info!("No municipality layer loaded, municipality lookups disabled");
+        }
+
+        self.summary.elapsed_ms = started.elapsed().as_millis();
+        info!(
+            "Loaded {} features from {} files in {} ms ({} skipped)",
+            self.summary.total_loaded(),
+            self.summary.files.len(),
+            self.summary.elapsed_ms,
+            self.summary.total_skipped()
+        );
     
         Ok(())
     }
 
+    /// Carga solo departamentos y municipios, para fuentes que traen rutas y paradas de otro lado.
+    pub fn load_boundaries(&mut self) -> Result<(), LoaderError> {
+        self.summary = LoadSummary::default();
+        let manifest = self.manifest()?;
+        self.departments = self.load_layers(&manifest, LayerKind::Departments)?;
+        self.municipalities = self.load_layers(&manifest, LayerKind::Municipalities)?;
+        Ok(())
+    }
+
+    fn manifest(&self) -> Result<DataManifest, LoaderError> {
+        match &self.manifest {
+            Some(manifest) => Ok(manifest.clone()),
+            None => DataManifest::for_data_dir(&self.data_dir),
+        }
+    }
+
+    /// Une las features de todos los archivos de un tipo de capa.
+    fn load_layers<T: DeserializeOwned>(
+        &mut self,
+        manifest: &DataManifest,
+        kind: LayerKind,
+    ) -> Result<GeoJsonFeatureCollection<T>, LoaderError> {
+        let mut merged: GeoJsonFeatureCollection<T> = GeoJsonFeatureCollection::new("", Vec::new());
+        for layer in manifest.layers_of(kind) {
+            if layer.optional && !self.data_dir.join(&layer.path).exists() {
+                info!("Optional layer {} not found, skipping", layer.path.display());
+                continue;
+            }
+
+            let collection = self.load_geojson(layer)?;
+            if merged.features.is_empty() {
+                merged.r#type = collection.r#type;
+                merged.name = collection.name;
+                merged.crs = collection.crs;
+            }
+            merged.features.extend(collection.features);
+        }
+        Ok(merged)
+    }
+
     /// Carga un archivo GeoJSON específico
-    fn load_geojson<T: DeserializeOwned>(&self, filename: &str) -> Result<T, LoaderError> {
-        let file_path = self.data_dir.join(filename);
+    fn load_geojson<T: DeserializeOwned>(&mut self, layer: &LayerConfig) -> Result<GeoJsonFeatureCollection<T>, LoaderError> {
+        let file_path = self.data_dir.join(&layer.path);
+        let filename = layer.path.to_string_lossy().into_owned();
         info!("Loading {}", file_path.display());
+        let started = Instant::now();
     
         let file = File::open(&file_path)?;
         let reader = BufReader::new(file);
-    
-        // Agregar debug logging
-        let raw_json: serde_json::Value = serde_json::from_reader(reader)?;
-        debug!("Raw JSON structure: {}", raw_json);
-    
-        // Validar estructura básica
-        if !raw_json.is_object() || !raw_json.get("type").is_some() {
-            return Err(LoaderError::GeoJson("Invalid GeoJSON structure".into()));
-        }
-    
-        serde_json::from_value(raw_json)
-            .map_err(|e| {
-                error!("Failed to parse GeoJSON from {}: {}", filename, e);
-                LoaderError::Json(e)
-            })
-    }
-    
-    // Getters para acceder a los datos cargados
-    pub fn departments(&self) -> &DepartmentFeatureCollection {
-        &self.departments
-    }
 
-    pub fn bus_stops(&self) -> &BusStopFeatureCollection {
-        &self.bus_stops
+        let skipped_before = self.summary.skipped_features.len();
+        let collection: GeoJsonFeatureCollection<T> =
+            read_feature_collection(reader, &filename, &layer.properties, &mut self.summary.skipped_features)
+                .map_err(|e| {
+                    error!("Failed to parse GeoJSON from {}: {}", filename, e);
+                    e
+                })?;
+
+        let file_summary = FileLoadSummary {
+            file: filename.clone(),
+            loaded: collection.features.len(),
+            skipped: self.summary.skipped_features.len() - skipped_before,
+            elapsed_ms: started.elapsed().as_millis(),
+        };
+        info!(
+            "Loaded {} features from {} in {} ms ({} skipped)",
+            file_summary.loaded, filename, file_summary.elapsed_ms, file_summary.skipped
+        );
+        self.summary.files.push(file_summary);
+
+        Ok(collection)
     }
 
-    pub fn routes(&self) -> &RouteFeatureCollection {
-        &self.routes
+    pub fn summary(&self) -> &LoadSummary {
+        &self.summary
     }
 
     // Métodos de utilidad para consultas comunes
@@ -169,4 +367,107 @@ impl DataLoader {
             .filter(|route| route.subtipo == Some("INTERDEPARTAMENTAL".to_string()))
             .collect()
     }
-}
\ No newline at end of file
+}
+
+// Getters para acceder a los datos cargados
+impl PlannerDataSource for DataLoader {
+    fn departments(&self) -> &DepartmentFeatureCollection {
+        &self.departments
+    }
+
+    fn municipalities(&self) -> &MunicipalityFeatureCollection {
+        &self.municipalities
+    }
+
+    fn bus_stops(&self) -> &BusStopFeatureCollection {
+        &self.bus_stops
+    }
+
+    fn routes(&self) -> &RouteFeatureCollection {
+        &self.routes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan_routes::_structs::BusStopFeatureCollection;
+
+    #[test]
+    fn test_bad_features_are_skipped() {
+        let json = r#"{
+            "type": "FeatureCollection",
+            "name": "paradas",
+            "features": [
+                {"type": "Feature", "properties": {"Ruta": "1", "Latitud": 13.7, "Longitud": -89.2},
+                 "geometry": {"type": "Point", "coordinates": [-89.2, 13.7]}},
+                {"type": "Feature", "properties": {"Ruta": 5},
+                 "geometry": {"type": "Point", "coordinates": [-89.2, 13.7]}},
+                {"type": "Feature", "properties": {"Ruta": "2"},
+                 "geometry": {"type": "Point", "coordinates": [-89.1, 13.6]}}
+            ]
+        }"#;
+
+        let mut skipped = Vec::new();
+        let collection: BusStopFeatureCollection =
+            read_feature_collection(json.as_bytes(), "paradas.geojson", &BTreeMap::new(), &mut skipped).unwrap();
+
+        assert_eq!(collection.name, "paradas");
+        assert_eq!(collection.features.len(), 2);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].file, "paradas.geojson");
+        assert_eq!(skipped[0].index, 1);
+    }
+
+    #[test]
+    fn test_load_all_follows_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let empty = r#"{"type": "FeatureCollection", "features": []}"#;
+        std::fs::write(dir.path().join("deptos.geojson"), empty).unwrap();
+        std::fs::write(dir.path().join("paradas.geojson"), empty).unwrap();
+        std::fs::write(dir.path().join("alimentadoras.geojson"), r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {"type": "Feature", "properties": {"CODIGO": "AL01", "SENTIDO": "IDA"},
+                 "geometry": {"type": "LineString", "coordinates": [[-89.2, 13.7], [-89.1, 13.7]]}}
+            ]
+        }"#).unwrap();
+        std::fs::write(dir.path().join("manifest.toml"), r#"
+            [[layers]]
+            kind = "departments"
+            path = "deptos.geojson"
+
+            [[layers]]
+            kind = "municipalities"
+            path = "municipios.geojson"
+            optional = true
+
+            [[layers]]
+            kind = "stops"
+            path = "paradas.geojson"
+
+            [[layers]]
+            kind = "routes"
+            path = "alimentadoras.geojson"
+
+            [layers.properties]
+            "Código_de" = "CODIGO"
+        "#).unwrap();
+
+        let mut loader = DataLoader::new(dir.path());
+        loader.load_all().unwrap();
+
+        assert_eq!(loader.routes().features.len(), 1);
+        assert_eq!(loader.routes().features[0].properties.codigo_de.as_deref(), Some("AL01"));
+        assert!(loader.municipalities().features.is_empty());
+        assert_eq!(loader.summary().files.len(), 3);
+    }
+
+    #[test]
+    fn test_missing_type_is_an_error() {
+        let mut skipped = Vec::new();
+        let result: Result<BusStopFeatureCollection, _> =
+            read_feature_collection(r#"{"features": []}"#.as_bytes(), "x.geojson", &BTreeMap::new(), &mut skipped);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::_structs::{
+    BusStopFeatureCollection, DepartmentFeatureCollection, MunicipalityFeatureCollection,
+    RouteFeatureCollection,
+};
+
+/// Capas con las que se construye un planificador, vengan de archivos o de la base de datos.
+pub trait PlannerDataSource {
+    fn departments(&self) -> &DepartmentFeatureCollection;
+    fn municipalities(&self) -> &MunicipalityFeatureCollection;
+    fn bus_stops(&self) -> &BusStopFeatureCollection;
+    fn routes(&self) -> &RouteFeatureCollection;
+}
+
+/// De dónde lee una región sus rutas y paradas.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DataSourceKind {
+    /// Archivos GeoJSON del manifiesto.
+    #[default]
+    Files,
+    /// Tablas `routes`, `buses` y `stops`; los límites siguen viniendo del manifiesto.
+    Postgis,
+}
@@ -0,0 +1,174 @@
+use geo::HaversineDistance;
+use geo_types::Point;
+use serde_json::{json, Value};
+
+use super::_structs::RoutePlan;
+use super::spatial_search::SpatialSearch;
+use crate::queries::_structs::Route;
+
+fn point_feature(point: Point<f64>, properties: Value) -> Value {
+    json!({
+        "type": "Feature",
+        "properties": properties,
+        "geometry": { "type": "Point", "coordinates": [point.x(), point.y()] },
+    })
+}
+
+fn line_feature(coordinates: Vec<Vec<f64>>, properties: Value) -> Value {
+    json!({
+        "type": "Feature",
+        "properties": properties,
+        "geometry": { "type": "LineString", "coordinates": coordinates },
+    })
+}
+
+/// Tramo a pie en línea recta; `None` si no hay que caminar.
+fn walk_feature(from: Point<f64>, to: Point<f64>, plan: usize, segment: usize) -> Option<Value> {
+    let meters = from.haversine_distance(&to);
+    (meters > 0.0).then(|| {
+        line_feature(
+            vec![vec![from.x(), from.y()], vec![to.x(), to.y()]],
+            json!({ "kind": "walk", "plan": plan, "segment": segment, "distance_m": meters.round() }),
+        )
+    })
+}
+
+/// Features GeoJSON de una consulta: origen, destino y, por cada plan, los tramos a
+/// pie (`walk`), el recorrido sobre cada variante (`leg`) y los puntos de transbordo.
+pub fn plan_features(
+    search: &SpatialSearch,
+    origin: Point<f64>,
+    destination: Point<f64>,
+    plans: &[RoutePlan],
+) -> Vec<Value> {
+    let mut features = vec![
+        point_feature(origin, json!({ "kind": "origin" })),
+        point_feature(destination, json!({ "kind": "destination" })),
+    ];
+
+    for (rank, plan) in plans.iter().enumerate() {
+        let plan_number = rank + 1;
+        // Dónde está el pasajero antes de subir a cada variante
+        let mut position = origin;
+
+        for (index, segment) in plan.routes.iter().enumerate() {
+            let alight = segment.transfer_point.location;
+
+            if let Some(route) = search.route(&segment.variant_id) {
+                let board = route.geometry.closest_vertex(position).unwrap_or(position);
+                features.extend(walk_feature(position, board, plan_number, index + 1));
+
+                let coordinates = route
+                    .geometry
+                    .slice_between(board, alight)
+                    .unwrap_or_else(|| vec![vec![board.x(), board.y()], vec![alight.x(), alight.y()]]);
+                features.push(line_feature(
+                    coordinates,
+                    json!({
+                        "kind": "leg",
+                        "plan": plan_number,
+                        "segment": index + 1,
+                        "route_code": segment.route.codigo_de,
+                        "route_name": segment.route.nombre_de,
+                        "direction": segment.route.sentido,
+                        "variant_id": segment.variant_id,
+                        "fees": segment.route.fees,
+                        "frequency": segment.route.frequency,
+                        "segment_distance": segment.segment_distance,
+                    }),
+                ));
+            }
+
+            if index + 1 < plan.routes.len() {
+                features.push(point_feature(
+                    alight,
+                    json!({
+                        "kind": "transfer",
+                        "plan": plan_number,
+                        "segment": index + 1,
+                        "transfer_type": segment.transfer_point.transfer_type,
+                        "stop_name": segment.transfer_point.bus_stop.as_ref().and_then(|stop| stop.nam.clone()),
+                        "distance": segment.transfer_point.distance_to_route,
+                    }),
+                ));
+            }
+            position = alight;
+        }
+
+        features.extend(walk_feature(position, destination, plan_number, plan.routes.len() + 1));
+    }
+
+    features
+}
+
+/// Una feature por ruta de la base, con sus datos como propiedades.
+pub fn route_features(routes: &[Route]) -> Vec<Value> {
+    routes
+        .iter()
+        .map(|route| {
+            json!({
+                "type": "Feature",
+                "id": route.route_id,
+                "properties": {
+                    "route_id": route.route_id,
+                    "bus_id": route.bus_id,
+                    "direction_id": route.direction_id,
+                    "number": route.number_route,
+                    "code": route.code_route,
+                    "fees": route.fees,
+                    "special_fees": route.special_fees,
+                    "frequency": route.frequency,
+                    "distance": route.distance,
+                    "photo_url": route.photo_url,
+                },
+                "geometry": route.route_geometry,
+            })
+        })
+        .collect()
+}
+
+pub fn feature_collection(features: Vec<Value>) -> Value {
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan_routes::_structs::{RouteSegment, TransferPoint, TransferType};
+    use crate::plan_routes::fixtures::route;
+
+    #[test]
+    fn test_plan_legs_and_walks() {
+        let mut line = route("A", vec![vec![-89.20, 13.70], vec![-89.19, 13.70], vec![-89.18, 13.70], vec![-89.17, 13.70]]);
+        line.properties.fees = Some(0.25);
+        let search = SpatialSearch::without_cache(vec![line.clone()], vec![]);
+
+        let mut plan = RoutePlan::new();
+        plan.add_segment(RouteSegment {
+            variant_id: "A/-".into(),
+            route: line.properties.clone(),
+            transfer_type: TransferType::Direct,
+            transfer_point: TransferPoint {
+                location: Point::new(-89.18, 13.70),
+                bus_stop: None,
+                distance_to_route: 0.0,
+                transfer_type: TransferType::Direct,
+                from_route: "A".into(),
+                to_route: String::new(),
+            },
+            segment_distance: 0.0,
+        });
+
+        let features = plan_features(&search, Point::new(-89.19, 13.701), Point::new(-89.18, 13.702), &[plan]);
+        let kinds: Vec<&str> = features.iter().map(|f| f["properties"]["kind"].as_str().unwrap()).collect();
+        assert_eq!(kinds, ["origin", "destination", "walk", "leg", "walk"]);
+
+        // El tramo en bus solo cubre lo recorrido, no toda la variante
+        let leg = &features[3];
+        assert_eq!(leg["geometry"]["coordinates"], json!([[-89.19, 13.70], [-89.18, 13.70]]));
+        assert_eq!(leg["properties"]["fees"], json!(0.25));
+    }
+}
@@ -0,0 +1,115 @@
+//! Datos de prueba compartidos por los tests del planificador.
+
+use super::_structs::{
+    BusStopProperties, DepartmentProperties, GeoJsonFeature, GeoJsonFeatureCollection, GeoJsonGeometry,
+    MunicipalityProperties, RouteProperties,
+};
+
+/// Propiedades de ruta con solo código y sentido; el resto vacío.
+fn route_properties(code: Option<&str>, sentido: Option<&str>) -> RouteProperties {
+    RouteProperties {
+        codigo_de: code.map(String::from),
+        nombre_de: None,
+        sentido: sentido.map(String::from),
+        tipo: None,
+        subtipo: None,
+        departamento: None,
+        kilometro: None,
+        cantidad_d: None,
+        shape_leng: None,
+        fees: None,
+        frequency: None,
+    }
+}
+
+fn route_feature(properties: RouteProperties, geometry: GeoJsonGeometry) -> GeoJsonFeature<RouteProperties> {
+    GeoJsonFeature {
+        r#type: "Feature".into(),
+        properties,
+        geometry,
+    }
+}
+
+/// Ruta `LineString` sin sentido, la forma más común en los tests.
+pub fn route(code: &str, coordinates: Vec<Vec<f64>>) -> GeoJsonFeature<RouteProperties> {
+    variant(Some(code), None, GeoJsonGeometry::LineString { coordinates })
+}
+
+/// Variante con cualquier geometría; código y sentido opcionales.
+pub fn variant(code: Option<&str>, sentido: Option<&str>, geometry: GeoJsonGeometry) -> GeoJsonFeature<RouteProperties> {
+    route_feature(route_properties(code, sentido), geometry)
+}
+
+pub fn bus_stop(route: &str, lng: f64, lat: f64) -> BusStopProperties {
+    BusStopProperties {
+        fid_l0coor: None,
+        ruta: Some(route.to_string()),
+        cod: None,
+        coordenada: None,
+        latitud: Some(lat),
+        longitud: Some(lng),
+        fcode: None,
+        na2: None,
+        na3: None,
+        nam: None,
+    }
+}
+
+/// Parada como feature `Point`, tal como viene en la capa de paradas.
+pub fn bus_stop_feature(route: &str, lng: f64, lat: f64) -> GeoJsonFeature<BusStopProperties> {
+    GeoJsonFeature {
+        r#type: "Feature".into(),
+        properties: bus_stop(route, lng, lat),
+        geometry: GeoJsonGeometry::Point { coordinates: vec![lng, lat] },
+    }
+}
+
+pub fn collection<T>(features: Vec<GeoJsonFeature<T>>) -> GeoJsonFeatureCollection<T> {
+    GeoJsonFeatureCollection::new("test", features)
+}
+
+/// Polígono rectangular, suficiente para probar contención.
+pub fn square(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> GeoJsonGeometry {
+    GeoJsonGeometry::Polygon {
+        coordinates: vec![vec![
+            vec![min_x, min_y],
+            vec![max_x, min_y],
+            vec![max_x, max_y],
+            vec![min_x, max_y],
+            vec![min_x, min_y],
+        ]],
+    }
+}
+
+pub fn department(name: &str, geometry: GeoJsonGeometry) -> GeoJsonFeature<DepartmentProperties> {
+    GeoJsonFeature {
+        r#type: "Feature".into(),
+        properties: DepartmentProperties {
+            fcode: None,
+            cod: None,
+            na2: None,
+            na3: None,
+            nam: name.into(),
+            area_km: None,
+            perimetro: None,
+            shape_leng: None,
+            shape_area: None,
+        },
+        geometry,
+    }
+}
+
+pub fn municipality(na2: Option<&str>, na3: &str, geometry: GeoJsonGeometry) -> GeoJsonFeature<MunicipalityProperties> {
+    GeoJsonFeature {
+        r#type: "Feature".into(),
+        properties: MunicipalityProperties {
+            fcode: None,
+            cod: None,
+            na2: na2.map(String::from),
+            na3: Some(na3.into()),
+            nam: None,
+            area_km: None,
+        },
+        geometry,
+    }
+}
@@ -1,18 +1,79 @@
-use geo::{Point, MultiPolygon, Coord};
+use geo::{Point, MultiPolygon, Coord, Line};
+use geo::algorithm::bounding_rect::BoundingRect;
 use geo::algorithm::contains::Contains;
 use geo::algorithm::euclidean_distance::EuclideanDistance;
-use tracing::{error};
+use geo::algorithm::interior_point::InteriorPoint;
+use geo::algorithm::intersects::Intersects;
+use serde::{Deserialize, Serialize};
+use std::env;
+use tracing::warn;
+use utoipa::ToSchema;
 use crate::plan_routes::_structs::*;
 use geo_types::Polygon;
 
+/// Kilómetros aproximados por grado en el ecuador, para reportar distancias.
+const KM_PER_DEGREE: f64 = 111.32;
+
 pub struct GeoValidator {
     departments: Vec<DepartmentBoundary>,
+    service_area: ServiceAreaConfig,
+}
+
+/// Parámetros del área de servicio: la unión de los límites cargados más un margen.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServiceAreaConfig {
+    pub buffer: f64,          // margen aceptado fuera de los límites (~1km en grados)
+    pub border_margin: f64,   // franja adicional reportada como "cerca de la frontera" (~5km)
+    pub coast_to_south: bool, // la costa queda al sur del área (Pacífico)
+}
+
+impl Default for ServiceAreaConfig {
+    fn default() -> Self {
+        Self {
+            buffer: 0.01,
+            border_margin: 0.05,
+            coast_to_south: true,
+        }
+    }
+}
+
+impl ServiceAreaConfig {
+    /// Lee `SERVICE_AREA_BUFFER` y `SERVICE_AREA_BORDER_MARGIN` (en grados) y
+    /// `SERVICE_AREA_COAST_TO_SOUTH` (`true`/`false`) si están definidas.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Some(buffer) = env::var("SERVICE_AREA_BUFFER").ok().and_then(|v| v.parse().ok()) {
+            config.buffer = buffer;
+        }
+        if let Some(margin) = env::var("SERVICE_AREA_BORDER_MARGIN").ok().and_then(|v| v.parse().ok()) {
+            config.border_margin = margin;
+        }
+        if let Some(coast) = env::var("SERVICE_AREA_COAST_TO_SOUTH").ok().and_then(|v| v.parse().ok()) {
+            config.coast_to_south = coast;
+        }
+        config
+    }
 }
 
 #[derive(Debug)]
 pub struct DepartmentBoundary {
     name: String,
     boundary: MultiPolygon<f64>,
+    municipalities: Vec<MunicipalityBoundary>,
+}
+
+#[derive(Debug)]
+pub struct MunicipalityBoundary {
+    name: String,
+    boundary: MultiPolygon<f64>,
+}
+
+/// Ubicación administrativa de un punto: departamento y municipio (distrito).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AdminArea {
+    pub department: Option<String>,
+    pub municipality: Option<String>,
 }
 
 #[derive(Debug)]
@@ -20,6 +81,8 @@ pub struct ValidationResult {
     pub is_valid: bool,
     pub origin_department: Option<String>,
     pub destination_department: Option<String>,
+    pub origin_municipality: Option<String>,
+    pub destination_municipality: Option<String>,
     pub is_interdepartmental: bool,
     pub distance_to_boundary: f64,
 }
@@ -28,73 +91,239 @@ pub struct ValidationResult {
 pub enum ValidationError {
     #[error("Invalid coordinates")]
     InvalidCoordinates,
-    #[error("Point outside country boundaries")]
-    OutsideCountry,
+    #[error("Point is in the ocean, {distance_km:.1} km off the coast")]
+    InOcean { distance_km: f64 },
+    #[error("Point is {distance_km:.1} km outside the service area, near the border")]
+    NearBorder { distance_km: f64 },
+    #[error("Point is outside the service area ({distance_km:.1} km away)")]
+    OutsideServiceArea { distance_km: f64 },
     #[error("Department not found")]
     DepartmentNotFound,
     #[error("Geometry error: {0}")]
     GeometryError(String),
 }
 
+impl ValidationError {
+    /// Motivo estable y legible por máquinas.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ValidationError::InvalidCoordinates => "invalid_coordinates",
+            ValidationError::InOcean { .. } => "in_ocean",
+            ValidationError::NearBorder { .. } => "near_border",
+            ValidationError::OutsideServiceArea { .. } => "outside_service_area",
+            ValidationError::DepartmentNotFound => "department_not_found",
+            ValidationError::GeometryError(_) => "geometry_error",
+        }
+    }
+}
+
+/// Convierte una geometría GeoJSON (Polygon o MultiPolygon) en un `MultiPolygon`.
+fn to_multipolygon(geometry: &GeoJsonGeometry) -> MultiPolygon<f64> {
+    fn ring(coords: &[Vec<f64>]) -> Vec<Coord<f64>> {
+        coords.iter()
+            .map(|coord| Coord { x: coord[0], y: coord[1] })
+            .collect()
+    }
+
+    fn polygon(rings: &[Vec<Vec<f64>>]) -> Option<Polygon<f64>> {
+        let (exterior, interiors) = rings.split_first()?;
+        Some(Polygon::new(
+            ring(exterior).into(),
+            interiors.iter().map(|interior| ring(interior).into()).collect(),
+        ))
+    }
+
+    match geometry {
+        GeoJsonGeometry::Polygon { coordinates } => {
+            MultiPolygon(polygon(coordinates).into_iter().collect())
+        }
+        GeoJsonGeometry::MultiPolygon { coordinates } => {
+            MultiPolygon(coordinates.iter().filter_map(|rings| polygon(rings)).collect())
+        }
+        _ => MultiPolygon(vec![]),
+    }
+}
+
+/// Normaliza nombres administrativos para compararlos (`NA2` vs `NAM`).
+fn normalize_name(name: &str) -> String {
+    name.trim().to_uppercase()
+}
+
 impl GeoValidator {
     pub fn new(department_collection: &DepartmentFeatureCollection) -> Self {
         let department_boundaries = department_collection.features
             .iter()
-            .map(|feature| {
-                let geometry = match &feature.geometry {
-                    GeoJsonGeometry::Polygon { coordinates } => {
-                        let exterior: Vec<Coord<f64>> = coordinates[0].iter()
-                            .map(|coord| Coord { x: coord[0], y: coord[1] })
-                            .collect();
-                        let interiors: Vec<Vec<Coord<f64>>> = coordinates[1..]
-                            .iter()
-                            .map(|interior| {
-                                interior.iter()
-                                    .map(|coord| Coord { x: coord[0], y: coord[1] })
-                                    .collect()
-                            })
-                            .collect();
-                        MultiPolygon(vec![Polygon::new(
-                            exterior.into(),
-                            interiors.into_iter().map(|i| i.into()).collect()
-                        )])
-                    },
-                    _ => MultiPolygon(vec![]),
-                };
-
-                DepartmentBoundary {
-                    name: feature.properties.nam.clone(),
-                    boundary: geometry,
-                }
+            .map(|feature| DepartmentBoundary {
+                name: feature.properties.nam.clone(),
+                boundary: to_multipolygon(&feature.geometry),
+                municipalities: Vec::new(),
             })
             .collect();
 
-        Self { departments: department_boundaries }
+        Self {
+            departments: department_boundaries,
+            service_area: ServiceAreaConfig::default(),
+        }
+    }
+
+    pub fn with_service_area(mut self, config: ServiceAreaConfig) -> Self {
+        self.service_area = config;
+        self
+    }
+
+    pub fn service_area(&self) -> &ServiceAreaConfig {
+        &self.service_area
     }
 
+    /// Indica si algún departamento cargado contiene el punto.
+    pub fn contains(&self, point: Point<f64>) -> bool {
+        self.departments.iter().any(|dept| dept.boundary.contains(&point))
+    }
+
+    /// Agrega la capa de municipios, anidando cada uno bajo su departamento.
+    ///
+    /// El departamento se resuelve por `NA2`; si no coincide con ningún nombre,
+    /// se usa el departamento que contiene un punto interior del municipio.
+    pub fn with_municipalities(mut self, municipality_collection: &MunicipalityFeatureCollection) -> Self {
+        let mut skipped = 0;
+
+        for feature in &municipality_collection.features {
+            let Some(name) = feature.properties.name() else {
+                skipped += 1;
+                continue;
+            };
+            let boundary = to_multipolygon(&feature.geometry);
+
+            let by_name = feature.properties.na2.as_deref().and_then(|na2| {
+                let na2 = normalize_name(na2);
+                self.departments.iter().position(|dept| normalize_name(&dept.name) == na2)
+            });
+            let parent = by_name.or_else(|| {
+                let inner = boundary.interior_point()?;
+                self.departments.iter().position(|dept| dept.boundary.contains(&inner))
+            });
+
+            match parent {
+                Some(idx) => self.departments[idx].municipalities.push(MunicipalityBoundary {
+                    name: name.to_string(),
+                    boundary,
+                }),
+                None => skipped += 1,
+            }
+        }
+
+        if skipped > 0 {
+            warn!("{} municipalities could not be assigned to a department", skipped);
+        }
+
+        self
+    }
+
+    pub fn departments_count(&self) -> usize {
+        self.departments.len()
+    }
+
+    pub fn municipalities_count(&self) -> usize {
+        self.departments.iter().map(|dept| dept.municipalities.len()).sum()
+    }
+
+    /// Determina el departamento y municipio que contienen el punto.
+    pub fn locate(&self, point: Point<f64>) -> AdminArea {
+        let Some(dept) = self.departments.iter().find(|dept| dept.boundary.contains(&point)) else {
+            return AdminArea::default();
+        };
+
+        AdminArea {
+            department: Some(dept.name.clone()),
+            municipality: dept.municipalities
+                .iter()
+                .find(|municipality| municipality.boundary.contains(&point))
+                .map(|municipality| municipality.name.clone()),
+        }
+    }
+
+    /// Verifica que el punto sea una coordenada WGS84 válida (sin consultar límites).
+    pub fn check_coordinates(point: Point<f64>) -> Result<(), ValidationError> {
+        let (lng, lat) = (point.x(), point.y());
+        if lat.is_finite() && lng.is_finite()
+            && (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lng)
+        {
+            Ok(())
+        } else {
+            Err(ValidationError::InvalidCoordinates)
+        }
+    }
+
+    /// Valida un punto contra el área de servicio.
+    ///
+    /// Devuelve el departamento que lo contiene, o `None` si cae dentro del margen
+    /// (`buffer`) alrededor de los límites. Fuera del margen se reporta el motivo.
     pub fn validate_point(&self, point: Point<f64>) -> Result<Option<String>, ValidationError> {
+        Self::check_coordinates(point)?;
+
+        // Sin límites cargados no hay forma de restringir el punto
+        if self.departments.is_empty() {
+            return Ok(None);
+        }
+
         for dept in &self.departments {
             if dept.boundary.contains(&point) {
                 return Ok(Some(dept.name.clone()));
             }
         }
 
-        let min_distance = self.departments
-            .iter()
-            .map(|dept| dept.boundary.euclidean_distance(&point))
-            .min_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap_or(f64::MAX);
+        let min_distance = self.distance_to_service_area(point);
+        if min_distance <= self.service_area.buffer {
+            return Ok(None);
+        }
 
-        if min_distance < 0.01 {
-            Ok(None)
+        let distance_km = min_distance * KM_PER_DEGREE;
+        if self.service_area.coast_to_south && self.is_offshore(point) {
+            Err(ValidationError::InOcean { distance_km })
+        } else if min_distance <= self.service_area.buffer + self.service_area.border_margin {
+            Err(ValidationError::NearBorder { distance_km })
         } else {
-            Err(ValidationError::OutsideCountry)
+            Err(ValidationError::OutsideServiceArea { distance_km })
         }
     }
 
+    fn distance_to_service_area(&self, point: Point<f64>) -> f64 {
+        self.departments
+            .iter()
+            .map(|dept| dept.boundary.euclidean_distance(&point))
+            .min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .unwrap_or(f64::MAX)
+    }
+
+    /// Heurística de costa: hay tierra del área al norte del punto y ninguna al sur.
+    fn is_offshore(&self, point: Point<f64>) -> bool {
+        let Some((min_y, max_y)) = self.departments
+            .iter()
+            .filter_map(|dept| dept.boundary.bounding_rect())
+            .map(|rect| (rect.min().y, rect.max().y))
+            .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+        else {
+            return false;
+        };
+
+        let land_along = |to_y: f64| {
+            let ray = Line::new(point.0, Coord { x: point.x(), y: to_y });
+            self.departments.iter().any(|dept| dept.boundary.intersects(&ray))
+        };
+
+        point.y() < max_y && land_along(max_y) && (point.y() <= min_y || !land_along(min_y))
+    }
+
     pub fn validate_route(&self, origin: Point<f64>, destination: Point<f64>) -> Result<ValidationResult, ValidationError> {
-        let origin_dept = self.validate_point(origin)?;
-        let dest_dept = self.validate_point(destination)?;
+        // Los puntos aceptados dentro del margen se asignan al departamento más cercano
+        let origin_dept = match self.validate_point(origin)? {
+            Some(dept) => Some(dept),
+            None => self.get_nearest_department(origin).ok(),
+        };
+        let dest_dept = match self.validate_point(destination)? {
+            Some(dept) => Some(dept),
+            None => self.get_nearest_department(destination).ok(),
+        };
 
         let distance_to_boundary = self.departments
             .iter()
@@ -111,21 +340,18 @@ impl GeoValidator {
             is_valid: origin_dept.is_some() && dest_dept.is_some(),
             origin_department: origin_dept,
             destination_department: dest_dept,
+            origin_municipality: self.locate(origin).municipality,
+            destination_municipality: self.locate(destination).municipality,
             is_interdepartmental,
             distance_to_boundary,
         })
     }
 
     pub fn get_route_departments(&self, route_feature: &GeoJsonFeature<RouteProperties>) -> Vec<String> {
-        let coords = match &route_feature.geometry {
-            GeoJsonGeometry::LineString { coordinates } => coordinates,
-            _ => return vec![],
-        };
-
         self.departments
             .iter()
             .filter(|dept| {
-                coords.iter().any(|coord| {
+                route_feature.geometry.line_coords().any(|coord| {
                     let point = Point::new(coord[0], coord[1]);
                     dept.boundary.contains(&point)
                 })
@@ -156,23 +382,14 @@ impl GeoValidator {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::plan_routes::fixtures::{collection, department, municipality, square};
 
     #[test]
     fn test_point_validation() {
         let point = Point::new(-89.2182, 13.6929);
         
         // Use empty DepartmentFeatureCollection for tests
-        let empty_collection = DepartmentFeatureCollection {
-            r#type: String::new(),
-            name: String::new(),
-            crs: GeoJsonCrs {
-                r#type: String::new(),
-                properties: GeoJsonCrsProperties {
-                    name: String::new(),
-                },
-            },
-            features: vec![],
-        };
+        let empty_collection = collection(vec![]);
         
         let validator = GeoValidator::new(&empty_collection);
         let result = validator.validate_point(point);
@@ -185,20 +402,67 @@ mod tests {
         let destination = Point::new(-89.1821, 13.7084);
         
         // Use empty DepartmentFeatureCollection for tests
-        let empty_collection = DepartmentFeatureCollection {
-            r#type: String::new(),
-            name: String::new(),
-            crs: GeoJsonCrs {
-                r#type: String::new(),
-                properties: GeoJsonCrsProperties {
-                    name: String::new(),
-                },
-            },
-            features: vec![],
-        };
+        let empty_collection = collection(vec![]);
         
         let validator = GeoValidator::new(&empty_collection);
         let result = validator.validate_route(origin, destination);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_locate_municipality() {
+        let departments = collection(vec![department("SAN SALVADOR", square(0.0, 0.0, 2.0, 1.0))]);
+        let municipalities = collection(vec![
+            municipality(Some("San Salvador"), "SAN SALVADOR CENTRO", square(0.0, 0.0, 1.0, 1.0)),
+            // Sin NA2: se asigna por contención
+            municipality(None, "SOYAPANGO", square(1.0, 0.0, 2.0, 1.0)),
+            // Fuera de todo departamento: se descarta
+            municipality(None, "FUERA", square(5.0, 5.0, 6.0, 6.0)),
+        ]);
+
+        let validator = GeoValidator::new(&departments).with_municipalities(&municipalities);
+        assert_eq!(validator.municipalities_count(), 2);
+
+        let area = validator.locate(Point::new(1.5, 0.5));
+        assert_eq!(area.department.as_deref(), Some("SAN SALVADOR"));
+        assert_eq!(area.municipality.as_deref(), Some("SOYAPANGO"));
+
+        assert_eq!(validator.locate(Point::new(10.0, 10.0)), AdminArea::default());
+    }
+
+    #[test]
+    fn test_service_area_reasons() {
+        let departments = collection(vec![
+            department("OESTE", square(0.0, 0.0, 1.0, 1.0)),
+            department("ESTE", square(1.0, 0.0, 2.0, 1.0)),
+        ]);
+        let validator = GeoValidator::new(&departments);
+
+        assert_eq!(validator.validate_point(Point::new(1.5, 0.5)).unwrap().as_deref(), Some("ESTE"));
+        // Dentro del margen: aceptado sin departamento
+        assert_eq!(validator.validate_point(Point::new(1.5, 1.005)).unwrap(), None);
+
+        let reason = |x: f64, y: f64| validator.validate_point(Point::new(x, y)).unwrap_err().reason();
+        assert_eq!(reason(1.5, -0.2), "in_ocean");
+        assert_eq!(reason(1.5, 1.03), "near_border");
+        assert_eq!(reason(1.5, 3.0), "outside_service_area");
+        assert_eq!(reason(10.0, 0.5), "outside_service_area");
+        assert_eq!(reason(1.5, 95.0), "invalid_coordinates");
+    }
+
+    #[test]
+    fn test_route_validation_within_buffer() {
+        let departments = collection(vec![
+            department("OESTE", square(0.0, 0.0, 1.0, 1.0)),
+            department("ESTE", square(1.0, 0.0, 2.0, 1.0)),
+        ]);
+        let validator = GeoValidator::new(&departments);
+
+        let result = validator
+            .validate_route(Point::new(0.5, 0.5), Point::new(1.5, 1.005))
+            .unwrap();
+        assert!(result.is_valid);
+        assert_eq!(result.destination_department.as_deref(), Some("ESTE"));
+        assert!(result.is_interdepartmental);
+    }
 }
@@ -1,10 +1,14 @@
 use super::{
     geo_validation::{GeoValidator, ValidationResult},
+    matrix::{travel_matrix, TravelMatrix},
     spatial_search::{SpatialSearch, SearchError}
 };
 use crate::plan_routes::_structs::*;
+use crate::metrics::{observe_candidate_plans, time_stage, PlannerStage};
+use crate::utils::result_cache::{CacheConfig, CacheStats, ResultCache};
 use geo_types::Point;
-use tracing::{debug, info, warn, error};
+use serde::{Deserialize, Serialize};
+use tracing::{info, error};
 
 #[derive(Debug, thiserror::Error)]
 pub enum PlanningError {
@@ -18,7 +22,8 @@ pub enum PlanningError {
     NoValidRoutes,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PlanningConfig {
     pub max_route_distance: f64,    // 5km para encontrar rutas cercanas
     pub max_transfer_distance: f64, // 1km para transbordos próximos
@@ -37,10 +42,37 @@ impl Default for PlanningConfig {
     }
 }
 
+/// Plan candidato con su puntaje (menor es mejor), para `--explain`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoredPlan {
+    pub variant_ids: Vec<String>,
+    pub transfers_count: i32,
+    pub total_distance: f64,
+    pub score: f64,
+}
+
+/// Detalle de cómo se llegó a los planes de una consulta.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanExplanation {
+    pub origin_department: Option<String>,
+    pub destination_department: Option<String>,
+    pub is_interdepartmental: bool,
+    pub max_route_distance: f64,
+    pub max_transfers: i32,
+    pub origin_candidates: Vec<String>,
+    pub destination_candidates: Vec<String>,
+    pub plans: Vec<ScoredPlan>,
+    pub error: Option<String>,
+}
+
+/// Llave del caché de planes: origen y destino ajustados a la grilla.
+type PlanKey = ((i64, i64), (i64, i64));
+
 pub struct RoutePlanner {
     config: PlanningConfig,
     validator: GeoValidator,
     search: SpatialSearch,
+    cache: ResultCache<PlanKey, Vec<RoutePlan>>,
 }
 
 impl RoutePlanner {
@@ -53,44 +85,59 @@ impl RoutePlanner {
             config: config.unwrap_or_default(),
             validator,
             search,
+            cache: ResultCache::new(CacheConfig { capacity: 0, ttl: Default::default(), grid: 1.0 }),
         }
     }
 
-    /// Planifica rutas entre dos puntos
+    /// Guarda los planes encontrados. El caché vive con el planificador, así que se
+    /// descarta cuando se recargan los datos y se construye uno nuevo; `name` etiqueta
+    /// sus métricas, que sí se conservan.
+    pub fn with_cache(mut self, config: CacheConfig, name: &str) -> Self {
+        self.cache = ResultCache::new(config).named(name);
+        self
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Planifica rutas entre dos puntos. Los puntos a menos de una celda de la grilla
+    /// del caché comparten resultado.
     pub fn plan_route(&self, origin: Point<f64>, destination: Point<f64>) 
         -> Result<Vec<RoutePlan>, PlanningError> 
     {
-        // 1. Validar puntos y determinar contexto geográfico
-        info!("Validating geographic points");
-        let validation = self.validator.validate_route(origin, destination)?;
-        
-        if !validation.is_valid {
-            error!("Invalid points for route planning");
-            return Err(PlanningError::ValidationError(
-                crate::queries::plan_routes::geo_validation::ValidationError::InvalidCoordinates
-            ));
+        let key = (self.cache.config().snap(origin), self.cache.config().snap(destination));
+        if let Some(plans) = self.cache.get(&key) {
+            return Ok(plans);
         }
 
-        // 2. Crear request con configuración apropiada
-        let request = self.create_route_request(
-            origin, 
-            destination,
-            &validation
-        );
+        let plans = self.search_plans(origin, destination)?;
+        self.cache.insert(key, plans.clone());
+        Ok(plans)
+    }
+
+    fn search_plans(&self, origin: Point<f64>, destination: Point<f64>)
+        -> Result<Vec<RoutePlan>, PlanningError>
+    {
+        // 1 y 2. Validar puntos y crear request con configuración apropiada
+        let (validation, request) = time_stage(PlannerStage::Validation, || self.prepare(origin, destination))?;
 
         // 3. Buscar rutas posibles
         info!("Searching for possible routes");
         // debug!("Search request: {:?}", request);
         
-        let mut plans = self.search.find_routes_to_destination(
-            request.origin,
-            request.destination,
-            request.max_transfers,
-            request.max_route_distance,
-        ).map_err(PlanningError::SearchError)?;
+        let mut plans = time_stage(PlannerStage::Search, || {
+            self.search.find_routes_to_destination(
+                request.origin,
+                request.destination,
+                request.max_transfers,
+                request.max_route_distance,
+            )
+        }).map_err(PlanningError::SearchError)?;
+        observe_candidate_plans(plans.len());
 
         // 4. Optimizar y filtrar resultados
-        self.optimize_results(&validation, &mut plans);
+        time_stage(PlannerStage::Scoring, || self.optimize_results(&validation, &mut plans));
 
         // 5. Validar y retornar planes finales
         if plans.is_empty() {
@@ -100,6 +147,64 @@ impl RoutePlanner {
         Ok(plans.into_iter().take(self.config.results_limit).collect())
     }
 
+    /// Como [`RoutePlanner::plan_route`], pero devuelve las rutas candidatas y el
+    /// puntaje de cada plan encontrado en lugar de solo los mejores.
+    pub fn explain(&self, origin: Point<f64>, destination: Point<f64>) -> Result<PlanExplanation, PlanningError> {
+        let (validation, request) = self.prepare(origin, destination)?;
+
+        let mut explanation = PlanExplanation {
+            origin_department: validation.origin_department.clone(),
+            destination_department: validation.destination_department.clone(),
+            is_interdepartmental: validation.is_interdepartmental,
+            max_route_distance: request.max_route_distance,
+            max_transfers: request.max_transfers,
+            origin_candidates: self.search.nearby_route_ids(origin, request.max_route_distance),
+            destination_candidates: self.search.nearby_route_ids(destination, request.max_route_distance),
+            plans: Vec::new(),
+            error: None,
+        };
+
+        match self.search.find_routes_to_destination(
+            request.origin,
+            request.destination,
+            request.max_transfers,
+            request.max_route_distance,
+        ) {
+            Ok(plans) => {
+                explanation.plans = plans
+                    .iter()
+                    .map(|plan| ScoredPlan {
+                        variant_ids: plan.routes.iter().map(|segment| segment.variant_id.clone()).collect(),
+                        transfers_count: plan.transfers_count,
+                        total_distance: plan.total_distance,
+                        score: self.calculate_plan_score(plan, &validation),
+                    })
+                    .collect();
+                explanation.plans.sort_by(|a, b| a.score.partial_cmp(&b.score).unwrap_or(std::cmp::Ordering::Equal));
+            }
+            Err(e) => explanation.error = Some(e.to_string()),
+        }
+
+        Ok(explanation)
+    }
+
+    fn prepare(&self, origin: Point<f64>, destination: Point<f64>)
+        -> Result<(ValidationResult, RouteRequest), PlanningError>
+    {
+        info!("Validating geographic points");
+        let validation = self.validator.validate_route(origin, destination)?;
+
+        if !validation.is_valid {
+            error!("Invalid points for route planning");
+            return Err(PlanningError::ValidationError(
+                crate::queries::plan_routes::geo_validation::ValidationError::InvalidCoordinates
+            ));
+        }
+
+        let request = self.create_route_request(origin, destination, &validation);
+        Ok((validation, request))
+    }
+
     /// Crea una solicitud de ruta con la configuración apropiada
     fn create_route_request(&self, origin: Point<f64>, destination: Point<f64>, validation: &ValidationResult) 
         -> RouteRequest 
@@ -183,6 +288,11 @@ impl RoutePlanner {
         score
     }
 
+    /// Tiempos de viaje del mejor plan entre cada origen y cada destino; ver [`matrix`](super::matrix).
+    pub fn travel_matrix(&self, origins: &[Point<f64>], destinations: &[Point<f64>]) -> TravelMatrix {
+        time_stage(PlannerStage::Matrix, || travel_matrix(&self.search, &self.config, origins, destinations))
+    }
+
     // Métodos de utilidad para acceder a componentes internos si es necesario
     pub fn validator(&self) -> &GeoValidator {
         &self.validator
@@ -200,7 +310,7 @@ impl RoutePlanner {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use geo_types::Point;
+    use crate::plan_routes::fixtures::collection;
 
     // Helper para crear un plan de prueba
     fn create_test_plan(transfers: i32, distance: f64, is_interdept: bool) -> RoutePlan {
@@ -211,10 +321,44 @@ mod tests {
         plan
     }
 
+    fn create_test_planner() -> RoutePlanner {
+        let departments = collection(vec![]);
+        let cache_dir = tempfile::tempdir().unwrap();
+        let search = SpatialSearch::new(vec![], vec![], Some(cache_dir.path().to_path_buf()));
+        RoutePlanner::new(GeoValidator::new(&departments), search, None)
+    }
+
+    fn create_test_validation(is_interdept: bool) -> ValidationResult {
+        ValidationResult {
+            is_valid: true,
+            origin_department: None,
+            destination_department: None,
+            origin_municipality: None,
+            destination_municipality: None,
+            is_interdepartmental: is_interdept,
+            distance_to_boundary: f64::MAX,
+        }
+    }
+
     #[test]
     fn test_plan_scoring() {
-        // Implementar tests específicos para el scoring
-        // TODO: Agregar casos de prueba
+        let planner = create_test_planner();
+        let validation = create_test_validation(false);
+
+        // Menos transbordos siempre gana frente a una distancia algo mayor
+        let direct = create_test_plan(0, 0.04, false);
+        let with_transfer = create_test_plan(1, 0.01, false);
+        assert!(
+            planner.calculate_plan_score(&direct, &validation)
+                < planner.calculate_plan_score(&with_transfer, &validation)
+        );
+
+        // Penalización a rutas interdepartamentales innecesarias
+        let interdept = create_test_plan(0, 0.04, true);
+        assert!(
+            planner.calculate_plan_score(&direct, &validation)
+                < planner.calculate_plan_score(&interdept, &validation)
+        );
     }
 
     #[test]
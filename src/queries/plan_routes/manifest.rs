@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::data_loader::LoaderError;
+
+/// Nombre del manifiesto dentro de un directorio de datos.
+pub const MANIFEST_FILE: &str = "manifest.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayerKind {
+    Departments,
+    Municipalities,
+    Stops,
+    Routes,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayerFormat {
+    #[default]
+    Geojson,
+}
+
+/// Un archivo de datos: qué capa es, dónde está y cómo leer sus propiedades.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerConfig {
+    pub kind: LayerKind,
+    pub path: PathBuf,
+    #[serde(default)]
+    pub format: LayerFormat,
+    #[serde(default)]
+    pub optional: bool,
+    /// Propiedad esperada (p. ej. `Código_de`) -> propiedad en el archivo.
+    #[serde(default)]
+    pub properties: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataManifest {
+    /// Versión del conjunto de datos, reportada en `/api/status`
+    #[serde(default)]
+    pub version: Option<String>,
+    pub layers: Vec<LayerConfig>,
+}
+
+impl LayerConfig {
+    fn new(kind: LayerKind, path: &str, optional: bool) -> Self {
+        Self {
+            kind,
+            path: PathBuf::from(path),
+            format: LayerFormat::Geojson,
+            optional,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    pub fn map_properties(&self, properties: &mut Map<String, Value>) {
+        rename_properties(properties, &self.properties);
+    }
+
+    /// Ruta del archivo MessagePack generado por `convert_to_messagepack`.
+    pub fn binary_path(&self, base_dir: &Path) -> PathBuf {
+        base_dir.join(&self.path).with_extension("bin")
+    }
+}
+
+/// Renombra las propiedades del archivo a los nombres que esperan los tipos.
+pub fn rename_properties(properties: &mut Map<String, Value>, mapping: &BTreeMap<String, String>) {
+    for (expected, source) in mapping {
+        if let Some(value) = properties.remove(source) {
+            properties.insert(expected.clone(), value);
+        }
+    }
+}
+
+impl Default for DataManifest {
+    /// Los archivos históricos de `./data`.
+    fn default() -> Self {
+        Self {
+            version: None,
+            layers: vec![
+                LayerConfig::new(LayerKind::Departments, "LIM DEPARTAMENTALES.geojson", false),
+                LayerConfig::new(LayerKind::Municipalities, "LIM MUNICIPALES.geojson", true),
+                LayerConfig::new(LayerKind::Stops, "Paradas Transporte Colectivo AMSS.geojson", false),
+                LayerConfig::new(LayerKind::Routes, "Rutas Interdepartamentales.geojson", false),
+                LayerConfig::new(LayerKind::Routes, "Rutas Interurbanas.geojson", false),
+                LayerConfig::new(LayerKind::Routes, "Rutas Urbanas.geojson", false),
+            ],
+        }
+    }
+}
+
+impl DataManifest {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LoaderError> {
+        let content = fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// Lee `<data_dir>/manifest.toml`, o la lista histórica si no existe.
+    pub fn for_data_dir<P: AsRef<Path>>(data_dir: P) -> Result<Self, LoaderError> {
+        let path = data_dir.as_ref().join(MANIFEST_FILE);
+        if path.exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn layers_of(&self, kind: LayerKind) -> impl Iterator<Item = &LayerConfig> {
+        self.layers.iter().filter(move |layer| layer.kind == kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_parsing() {
+        let manifest: DataManifest = toml::from_str(r#"
+            [[layers]]
+            kind = "routes"
+            path = "Rutas Alimentadoras.geojson"
+            optional = true
+
+            [layers.properties]
+            "Código_de" = "CODIGO"
+
+            [[layers]]
+            kind = "stops"
+            path = "paradas.geojson"
+            format = "geojson"
+        "#).unwrap();
+
+        assert_eq!(manifest.layers.len(), 2);
+        assert_eq!(manifest.layers_of(LayerKind::Routes).count(), 1);
+        assert!(manifest.layers[0].optional);
+        assert!(!manifest.layers[1].optional);
+        assert_eq!(
+            manifest.layers[0].binary_path(Path::new("data")),
+            PathBuf::from("data/Rutas Alimentadoras.bin")
+        );
+
+        let mut properties = Map::new();
+        properties.insert("CODIGO".into(), Value::from("AB000A1LP"));
+        manifest.layers[0].map_properties(&mut properties);
+        assert_eq!(properties.get("Código_de"), Some(&Value::from("AB000A1LP")));
+        assert!(properties.get("CODIGO").is_none());
+    }
+
+    #[test]
+    fn test_unknown_format_is_rejected() {
+        let result: Result<DataManifest, _> = toml::from_str(r#"
+            [[layers]]
+            kind = "routes"
+            path = "rutas.shp"
+            format = "shapefile"
+        "#);
+        assert!(result.is_err());
+    }
+}
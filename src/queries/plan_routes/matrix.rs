@@ -0,0 +1,234 @@
+//! Matriz de tiempos de viaje entre varios orígenes y destinos.
+//!
+//! En lugar de planificar cada par, se hace una búsqueda por origen sobre el grafo
+//! de transbordos que deja, para cada variante alcanzable, la mejor forma de subir a
+//! ella. Cada destino se evalúa contra ese resultado con las variantes que pasan cerca.
+
+use geo::HaversineDistance;
+use geo_types::Point;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::_structs::{GeoJsonFeature, RouteProperties};
+use super::index::PlanningConfig;
+use super::spatial_search::SpatialSearch;
+
+/// Velocidad del bus, la misma que usa la estimación de `/api/plan_routes`.
+const BUS_SPEED_KMH: f64 = 30.0;
+/// Velocidad a pie.
+const WALKING_SPEED_KMH: f64 = 4.8;
+/// Minutos que se suman por cada transbordo.
+const TRANSFER_PENALTY_MIN: f64 = 5.0;
+/// Conversión aproximada de las distancias en grados del grafo de transbordos.
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Mejor plan encontrado para un par origen/destino.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MatrixCell {
+    pub minutes: f64,
+    pub transfers: i32,
+    pub walking_meters: f64,
+}
+
+/// `cells[i][j]` es el mejor plan del origen `i` al destino `j`, o `None` si no hay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TravelMatrix {
+    pub cells: Vec<Vec<Option<MatrixCell>>>,
+}
+
+/// Cómo se llega a subir a una variante desde el origen.
+#[derive(Debug, Clone, Copy)]
+struct Boarding {
+    transfers: i32,
+    /// Minutos acumulados hasta subir, caminata incluida
+    minutes: f64,
+    walking_meters: f64,
+    at: Point<f64>,
+}
+
+impl Boarding {
+    fn better_than(&self, other: &Boarding) -> bool {
+        (self.transfers, self.minutes) < (other.transfers, other.minutes)
+    }
+}
+
+fn walking_minutes(meters: f64) -> f64 {
+    meters / 1000.0 / WALKING_SPEED_KMH * 60.0
+}
+
+fn riding_minutes(meters: f64) -> f64 {
+    meters / 1000.0 / BUS_SPEED_KMH * 60.0
+}
+
+/// Vértice de la ruta más cercano al punto y su distancia en metros.
+fn closest_point(route: &GeoJsonFeature<RouteProperties>, point: Point<f64>) -> Option<(Point<f64>, f64)> {
+    let vertex = route.geometry.closest_vertex(point)?;
+    Some((vertex, vertex.haversine_distance(&point)))
+}
+
+/// Metros recorridos sobre la ruta entre los vértices más cercanos a `from` y `to`.
+fn ride_meters(route: &GeoJsonFeature<RouteProperties>, from: Point<f64>, to: Point<f64>) -> f64 {
+    route.geometry.slice_between(from, to).map_or(0.0, |coords| {
+        coords
+            .windows(2)
+            .map(|pair| Point::new(pair[0][0], pair[0][1]).haversine_distance(&Point::new(pair[1][0], pair[1][1])))
+            .sum()
+    })
+}
+
+/// Mejor forma de subir a cada variante alcanzable desde `origin` con hasta
+/// `max_transfers` transbordos. Se avanza por capas: cada capa es un transbordo más.
+fn reach_from(search: &SpatialSearch, origin: Point<f64>, config: &PlanningConfig) -> HashMap<String, Boarding> {
+    let mut reached: HashMap<String, Boarding> = HashMap::new();
+    let mut frontier = Vec::new();
+
+    for id in search.nearby_route_ids(origin, config.max_route_distance) {
+        let Some((at, walking_meters)) = search.route(&id).and_then(|route| closest_point(route, origin)) else {
+            continue;
+        };
+        reached.insert(id.clone(), Boarding { transfers: 0, minutes: walking_minutes(walking_meters), walking_meters, at });
+        frontier.push(id);
+    }
+
+    for transfers in 1..=config.max_transfers {
+        let mut next = Vec::new();
+        for id in &frontier {
+            let (Some(route), Some(boarding), Some(transfer_points)) =
+                (search.route(id), reached.get(id).copied(), search.intersections().get(id))
+            else {
+                continue;
+            };
+
+            for transfer in transfer_points {
+                let walk = transfer.distance_to_route * METERS_PER_DEGREE;
+                let candidate = Boarding {
+                    transfers,
+                    minutes: boarding.minutes
+                        + riding_minutes(ride_meters(route, boarding.at, transfer.location))
+                        + TRANSFER_PENALTY_MIN
+                        + walking_minutes(walk),
+                    walking_meters: boarding.walking_meters + walk,
+                    at: transfer.location,
+                };
+
+                let improved = reached
+                    .get(&transfer.to_route)
+                    .is_none_or(|current| candidate.better_than(current));
+                if improved {
+                    reached.insert(transfer.to_route.clone(), candidate);
+                    next.push(transfer.to_route.clone());
+                }
+            }
+        }
+
+        if next.is_empty() {
+            break;
+        }
+        next.sort();
+        next.dedup();
+        frontier = next;
+    }
+
+    reached
+}
+
+/// Variante cercana a un destino con el punto de bajada y la caminata final.
+struct Alighting {
+    route_id: String,
+    at: Point<f64>,
+    walking_meters: f64,
+}
+
+fn alightings(search: &SpatialSearch, destination: Point<f64>, config: &PlanningConfig) -> Vec<Alighting> {
+    search
+        .nearby_route_ids(destination, config.max_route_distance)
+        .into_iter()
+        .filter_map(|route_id| {
+            let (at, walking_meters) = closest_point(search.route(&route_id)?, destination)?;
+            Some(Alighting { route_id, at, walking_meters })
+        })
+        .collect()
+}
+
+/// Mejor plan (menos transbordos y luego menos minutos) de cada origen a cada destino.
+pub fn travel_matrix(
+    search: &SpatialSearch,
+    config: &PlanningConfig,
+    origins: &[Point<f64>],
+    destinations: &[Point<f64>],
+) -> TravelMatrix {
+    let destination_routes: Vec<Vec<Alighting>> = destinations
+        .iter()
+        .map(|destination| alightings(search, *destination, config))
+        .collect();
+
+    // Los hilos de rayon no heredan el span de quien llama; se entra en cada fila
+    let span = tracing::Span::current();
+    let cells = origins
+        .par_iter()
+        .map(|origin| {
+            let _entered = span.enter();
+            let reached = reach_from(search, *origin, config);
+            destination_routes
+                .iter()
+                .map(|candidates| {
+                    candidates
+                        .iter()
+                        .filter_map(|alighting| {
+                            let boarding = reached.get(&alighting.route_id)?;
+                            let route = search.route(&alighting.route_id)?;
+                            let cell = MatrixCell {
+                                minutes: boarding.minutes
+                                    + riding_minutes(ride_meters(route, boarding.at, alighting.at))
+                                    + walking_minutes(alighting.walking_meters),
+                                transfers: boarding.transfers,
+                                walking_meters: boarding.walking_meters + alighting.walking_meters,
+                            };
+                            Some(cell)
+                        })
+                        .min_by(|a, b| {
+                            (a.transfers, a.minutes)
+                                .partial_cmp(&(b.transfers, b.minutes))
+                                .unwrap_or(std::cmp::Ordering::Equal)
+                        })
+                })
+                .collect()
+        })
+        .collect();
+
+    TravelMatrix { cells }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan_routes::fixtures::route;
+
+    #[test]
+    fn test_matrix_with_transfer() {
+        // A va de oeste a este y se cruza con B, que sube hacia el norte
+        let routes = vec![
+            route("A", vec![vec![-89.20, 13.70], vec![-89.19, 13.70], vec![-89.18, 13.70]]),
+            route("B", vec![vec![-89.18, 13.70], vec![-89.18, 13.71], vec![-89.18, 13.72]]),
+        ];
+        let search = SpatialSearch::without_cache(routes, vec![]);
+        let config = PlanningConfig { max_route_distance: 0.002, ..PlanningConfig::default() };
+
+        let origins = [Point::new(-89.20, 13.7001)];
+        let destinations = [Point::new(-89.19, 13.7001), Point::new(-89.18, 13.7199), Point::new(-89.0, 13.0)];
+        let matrix = travel_matrix(&search, &config, &origins, &destinations);
+
+        let direct = matrix.cells[0][0].unwrap();
+        assert_eq!(direct.transfers, 0);
+        // ~1.08km en bus a 30 km/h más unos metros a pie
+        assert!(direct.minutes > 2.0 && direct.minutes < 2.5, "{:?}", direct);
+        assert!(direct.walking_meters < 30.0);
+
+        let with_transfer = matrix.cells[0][1].unwrap();
+        assert_eq!(with_transfer.transfers, 1);
+        assert!(with_transfer.minutes > direct.minutes + TRANSFER_PENALTY_MIN);
+
+        assert_eq!(matrix.cells[0][2], None);
+    }
+}
@@ -2,4 +2,16 @@ pub mod _structs;
 pub mod data_loader;
 pub mod geo_validation;
 pub mod spatial_search;
-pub mod index;
\ No newline at end of file
+pub mod index;
+pub mod region;
+pub mod quality;
+pub mod manifest;
+pub mod data_source;
+pub mod postgis;
+pub mod snapshot;
+pub mod export;
+pub mod matrix;
+pub mod tiles;
+
+#[cfg(test)]
+pub(crate) mod fixtures;
@@ -0,0 +1,153 @@
+use log::{info, warn};
+use tokio_postgres::{Client, Row};
+
+use super::_structs::{
+    BusStopFeatureCollection, BusStopProperties, DepartmentFeatureCollection, GeoJsonFeature,
+    GeoJsonFeatureCollection, GeoJsonGeometry, MunicipalityFeatureCollection, RouteFeatureCollection, RouteProperties,
+};
+use super::data_loader::LoaderError;
+use super::data_source::PlannerDataSource;
+
+const ROUTES_QUERY: &str = "
+    SELECT
+        r.id,
+        r.direction_id,
+        ST_AsGeoJSON(r.geometry)::TEXT AS route_geometry,
+        b.number_route,
+        b.code_route,
+        b.fees,
+        b.frequency::TEXT
+    FROM
+        routes r
+    JOIN
+        buses b ON r.bus_id = b.id;
+";
+
+// Una fila por parada y ruta, igual que en el GeoJSON de paradas.
+const STOPS_QUERY: &str = "
+    SELECT
+        s.id,
+        s.name,
+        ST_X(s.geometry) AS longitude,
+        ST_Y(s.geometry) AS latitude,
+        b.code_route
+    FROM
+        stops s
+    LEFT JOIN
+        stops_routes sr ON sr.stop_id = s.id
+    LEFT JOIN
+        routes r ON r.id = sr.route_id
+    LEFT JOIN
+        buses b ON r.bus_id = b.id;
+";
+
+/// Rutas y paradas leídas de PostGIS, con tarifas y frecuencias de `buses`.
+pub struct PostgisDataSource {
+    departments: DepartmentFeatureCollection,
+    municipalities: MunicipalityFeatureCollection,
+    bus_stops: BusStopFeatureCollection,
+    routes: RouteFeatureCollection,
+}
+
+fn route_feature(row: &Row) -> Result<GeoJsonFeature<RouteProperties>, serde_json::Error> {
+    let geometry_json: String = row.get(2);
+    let geometry: GeoJsonGeometry = serde_json::from_str(&geometry_json)?;
+    let direction_id: Option<i32> = row.get(1);
+
+    Ok(GeoJsonFeature {
+        r#type: "Feature".to_string(),
+        properties: RouteProperties {
+            codigo_de: Some(row.get(4)),
+            nombre_de: Some(row.get(3)),
+            sentido: direction_id.map(|id| id.to_string()),
+            tipo: None,
+            subtipo: None,
+            departamento: None,
+            kilometro: None,
+            cantidad_d: None,
+            shape_leng: None,
+            fees: row.get(5),
+            frequency: row.get(6),
+        },
+        geometry,
+    })
+}
+
+fn stop_feature(row: &Row) -> GeoJsonFeature<BusStopProperties> {
+    let longitude: f64 = row.get(2);
+    let latitude: f64 = row.get(3);
+
+    GeoJsonFeature {
+        r#type: "Feature".to_string(),
+        properties: BusStopProperties {
+            fid_l0coor: Some(row.get(0)),
+            ruta: row.get(4),
+            cod: None,
+            coordenada: None,
+            latitud: Some(latitude),
+            longitud: Some(longitude),
+            fcode: None,
+            na2: None,
+            na3: None,
+            nam: row.get(1),
+        },
+        geometry: GeoJsonGeometry::Point {
+            coordinates: vec![longitude, latitude],
+        },
+    }
+}
+
+impl PostgisDataSource {
+    /// Lee rutas y paradas de la base de datos. Los límites no están en la base,
+    /// así que se reciben ya cargados (ver [`super::data_loader::DataLoader::load_boundaries`]).
+    pub async fn load(
+        client: &Client,
+        departments: DepartmentFeatureCollection,
+        municipalities: MunicipalityFeatureCollection,
+    ) -> Result<Self, LoaderError> {
+        let mut routes = Vec::new();
+        for row in client.query(ROUTES_QUERY, &[]).await? {
+            match route_feature(&row) {
+                Ok(feature) => routes.push(feature),
+                Err(e) => {
+                    let id: i32 = row.get(0);
+                    warn!("Skipping route {}: unsupported geometry: {}", id, e);
+                }
+            }
+        }
+
+        let bus_stops: Vec<_> = client
+            .query(STOPS_QUERY, &[])
+            .await?
+            .iter()
+            .map(stop_feature)
+            .collect();
+
+        info!("Loaded {} routes and {} stops from PostGIS", routes.len(), bus_stops.len());
+
+        Ok(Self {
+            departments,
+            municipalities,
+            bus_stops: GeoJsonFeatureCollection::new("stops", bus_stops),
+            routes: GeoJsonFeatureCollection::new("routes", routes),
+        })
+    }
+}
+
+impl PlannerDataSource for PostgisDataSource {
+    fn departments(&self) -> &DepartmentFeatureCollection {
+        &self.departments
+    }
+
+    fn municipalities(&self) -> &MunicipalityFeatureCollection {
+        &self.municipalities
+    }
+
+    fn bus_stops(&self) -> &BusStopFeatureCollection {
+        &self.bus_stops
+    }
+
+    fn routes(&self) -> &RouteFeatureCollection {
+        &self.routes
+    }
+}
@@ -0,0 +1,357 @@
+use chrono::{DateTime, Utc};
+use geo::HaversineDistance;
+use geo_types::Point;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+use super::_structs::{
+    BusStopProperties, DepartmentProperties, GeoJsonFeature, GeoJsonGeometry, RouteProperties,
+};
+use super::data_source::PlannerDataSource;
+
+/// Metros por grado de latitud, para la proyección local equirectangular.
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Umbrales del reporte de calidad.
+#[derive(Debug, Clone)]
+pub struct QualityConfig {
+    pub max_stop_distance_m: f64, // distancia máxima parada-ruta
+    pub length_tolerance: f64,    // diferencia relativa tolerada en `Kilómetro`
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            max_stop_distance_m: 100.0,
+            length_tolerance: 0.15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    MissingRouteCode,
+    DuplicateRouteCode,
+    DegenerateRoute,
+    LengthMismatch,
+    UnknownStopRoute,
+    StopFarFromRoute,
+    InvalidBoundary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QualityIssue {
+    pub kind: IssueKind,
+    pub severity: Severity,
+    pub subject: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QualityReport {
+    pub generated_at: DateTime<Utc>,
+    pub routes: usize,
+    pub stops: usize,
+    pub departments: usize,
+    pub summary: BTreeMap<IssueKind, usize>,
+    pub issues: Vec<QualityIssue>,
+}
+
+fn length_km(parts: &[&[Vec<f64>]]) -> f64 {
+    parts
+        .iter()
+        .flat_map(|part| part.windows(2))
+        .map(|pair| {
+            Point::new(pair[0][0], pair[0][1]).haversine_distance(&Point::new(pair[1][0], pair[1][1]))
+        })
+        .sum::<f64>()
+        / 1000.0
+}
+
+/// Distancia en metros de un punto a una polilínea, proyectando localmente.
+fn distance_to_parts_m(lng: f64, lat: f64, parts: &[&[Vec<f64>]]) -> f64 {
+    let x_scale = METERS_PER_DEGREE * lat.to_radians().cos();
+    let project = |c: &[f64]| ((c[0] - lng) * x_scale, (c[1] - lat) * METERS_PER_DEGREE);
+
+    parts
+        .iter()
+        .flat_map(|part| {
+            let single = (part.len() == 1).then(|| (project(&part[0]), project(&part[0])));
+            part.windows(2)
+                .map(move |pair| (project(&pair[0]), project(&pair[1])))
+                .chain(single)
+        })
+        .map(|((ax, ay), (bx, by))| {
+            let (dx, dy) = (bx - ax, by - ay);
+            let len2 = dx * dx + dy * dy;
+            let t = if len2 == 0.0 { 0.0 } else { (-(ax * dx + ay * dy) / len2).clamp(0.0, 1.0) };
+            let (px, py) = (ax + t * dx, ay + t * dy);
+            (px * px + py * py).sqrt()
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// `Kilómetro` puede venir con coma decimal o con unidades ("12,5 km").
+fn parse_kilometers(value: &str) -> Option<f64> {
+    let cleaned: String = value
+        .trim()
+        .trim_end_matches(|c: char| c.is_alphabetic() || c.is_whitespace())
+        .replace(',', ".");
+    cleaned.parse().ok()
+}
+
+impl QualityReport {
+    pub fn from_source(source: &dyn PlannerDataSource, config: &QualityConfig) -> Self {
+        Self::build(
+            &source.routes().features,
+            &source.bus_stops().features,
+            &source.departments().features,
+            config,
+        )
+    }
+
+    pub fn build(
+        routes: &[GeoJsonFeature<RouteProperties>],
+        stops: &[GeoJsonFeature<BusStopProperties>],
+        departments: &[GeoJsonFeature<DepartmentProperties>],
+        config: &QualityConfig,
+    ) -> Self {
+        let mut issues = Vec::new();
+        let mut by_code: HashMap<&str, Vec<&GeoJsonFeature<RouteProperties>>> = HashMap::new();
+        let mut seen_variants: HashMap<(&str, Option<&str>), usize> = HashMap::new();
+
+        for (index, route) in routes.iter().enumerate() {
+            let parts = route.geometry.line_parts();
+            let subject = route.properties.codigo_de.clone()
+                .unwrap_or_else(|| format!("route #{}", index));
+
+            let Some(code) = route.properties.codigo_de.as_deref().filter(|code| !code.trim().is_empty()) else {
+                issues.push(QualityIssue {
+                    kind: IssueKind::MissingRouteCode,
+                    severity: Severity::Error,
+                    subject,
+                    message: format!(
+                        "Route #{} ({}) has no Código_de",
+                        index,
+                        route.properties.nombre_de.as_deref().unwrap_or("sin nombre")
+                    ),
+                });
+                continue;
+            };
+            by_code.entry(code).or_default().push(route);
+            *seen_variants.entry((code, route.properties.sentido.as_deref())).or_default() += 1;
+
+            let vertices: usize = parts.iter().map(|part| part.len()).sum();
+            if vertices < 2 {
+                issues.push(QualityIssue {
+                    kind: IssueKind::DegenerateRoute,
+                    severity: Severity::Error,
+                    subject,
+                    message: format!("Route {} has {} vertices", code, vertices),
+                });
+                continue;
+            }
+
+            if let Some(declared) = route.properties.kilometro.as_deref().and_then(parse_kilometers) {
+                let computed = length_km(&parts);
+                if declared > 0.0 && ((computed - declared) / declared).abs() > config.length_tolerance {
+                    issues.push(QualityIssue {
+                        kind: IssueKind::LengthMismatch,
+                        severity: Severity::Warning,
+                        subject,
+                        message: format!(
+                            "Route {} declares {:.2} km but its geometry measures {:.2} km",
+                            code, declared, computed
+                        ),
+                    });
+                }
+            }
+        }
+
+        let mut duplicates: Vec<_> = seen_variants.into_iter().filter(|(_, count)| *count > 1).collect();
+        duplicates.sort();
+        for ((code, sentido), count) in duplicates {
+            issues.push(QualityIssue {
+                kind: IssueKind::DuplicateRouteCode,
+                severity: Severity::Warning,
+                subject: code.to_string(),
+                message: format!(
+                    "Route code {} appears {} times with SENTIDO {}",
+                    code,
+                    count,
+                    sentido.unwrap_or("(vacío)")
+                ),
+            });
+        }
+
+        let known_codes: HashSet<&str> = by_code.keys().copied().collect();
+        for (index, stop) in stops.iter().enumerate() {
+            let Some(code) = stop.properties.ruta.as_deref() else {
+                continue;
+            };
+            let subject = format!("stop #{}", index);
+
+            if !known_codes.contains(code) {
+                issues.push(QualityIssue {
+                    kind: IssueKind::UnknownStopRoute,
+                    severity: Severity::Error,
+                    subject,
+                    message: format!("Stop #{} references unknown route {}", index, code),
+                });
+                continue;
+            }
+
+            let (Some(lng), Some(lat)) = (stop.properties.longitud, stop.properties.latitud) else {
+                continue;
+            };
+            let distance = by_code[code]
+                .iter()
+                .map(|route| distance_to_parts_m(lng, lat, &route.geometry.line_parts()))
+                .fold(f64::INFINITY, f64::min);
+            if distance > config.max_stop_distance_m {
+                issues.push(QualityIssue {
+                    kind: IssueKind::StopFarFromRoute,
+                    severity: Severity::Warning,
+                    subject,
+                    message: format!(
+                        "Stop #{} is {:.0} m from route {} (max {:.0} m)",
+                        index, distance, code, config.max_stop_distance_m
+                    ),
+                });
+            }
+        }
+
+        for department in departments {
+            let valid = match &department.geometry {
+                GeoJsonGeometry::Polygon { coordinates } => coordinates.first().is_some_and(|ring| ring.len() >= 4),
+                GeoJsonGeometry::MultiPolygon { coordinates } => !coordinates.is_empty(),
+                _ => false,
+            };
+            if !valid {
+                issues.push(QualityIssue {
+                    kind: IssueKind::InvalidBoundary,
+                    severity: Severity::Error,
+                    subject: department.properties.nam.clone(),
+                    message: format!("Department {} has no usable polygon", department.properties.nam),
+                });
+            }
+        }
+
+        let mut summary = BTreeMap::new();
+        for issue in &issues {
+            *summary.entry(issue.kind).or_insert(0) += 1;
+        }
+
+        Self {
+            generated_at: Utc::now(),
+            routes: routes.len(),
+            stops: stops.len(),
+            departments: departments.len(),
+            summary,
+            issues,
+        }
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Error).count()
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Warning).count()
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# Reporte de calidad de datos\n");
+        let _ = writeln!(out, "Generado: {}\n", self.generated_at.to_rfc3339());
+        let _ = writeln!(
+            out,
+            "- Rutas: {}\n- Paradas: {}\n- Departamentos: {}\n- Errores: {}\n- Advertencias: {}\n",
+            self.routes,
+            self.stops,
+            self.departments,
+            self.error_count(),
+            self.warning_count()
+        );
+
+        if self.issues.is_empty() {
+            let _ = writeln!(out, "Sin problemas encontrados.");
+            return out;
+        }
+
+        let _ = writeln!(out, "## Resumen\n\n| Tipo | Cantidad |\n|---|---|");
+        for (kind, count) in &self.summary {
+            let _ = writeln!(out, "| {:?} | {} |", kind, count);
+        }
+
+        let _ = writeln!(out, "\n## Detalle\n\n| Severidad | Tipo | Elemento | Detalle |\n|---|---|---|---|");
+        for issue in &self.issues {
+            let _ = writeln!(
+                out,
+                "| {:?} | {:?} | {} | {} |",
+                issue.severity,
+                issue.kind,
+                issue.subject.replace('|', "\\|"),
+                issue.message.replace('|', "\\|")
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan_routes::fixtures::{bus_stop_feature, variant};
+
+    #[test]
+    fn test_quality_issues() {
+        // ~1.1 km hacia el este
+        let line = || GeoJsonGeometry::LineString {
+            coordinates: vec![vec![-89.20, 13.70], vec![-89.19, 13.70]],
+        };
+        let mut measured = variant(Some("R1"), Some("IDA"), line());
+        measured.properties.kilometro = Some("1,08".into());
+        let mut too_long = variant(Some("R3"), Some("IDA"), line());
+        too_long.properties.kilometro = Some("5".into());
+        let routes = vec![
+            measured,
+            variant(Some("R1"), Some("IDA"), line()),
+            variant(Some("R1"), Some("REGRESO"), line()),
+            variant(None, Some("IDA"), line()),
+            variant(Some("R2"), Some("IDA"), GeoJsonGeometry::LineString { coordinates: vec![vec![-89.20, 13.70]] }),
+            too_long,
+        ];
+        let stops = vec![
+            bus_stop_feature("R1", -89.195, 13.7001),
+            bus_stop_feature("R1", -89.195, 13.71),
+            bus_stop_feature("R9", -89.195, 13.70),
+        ];
+
+        let report = QualityReport::build(&routes, &stops, &[], &QualityConfig::default());
+
+        assert_eq!(report.summary.get(&IssueKind::MissingRouteCode), Some(&1));
+        assert_eq!(report.summary.get(&IssueKind::DuplicateRouteCode), Some(&1));
+        assert_eq!(report.summary.get(&IssueKind::DegenerateRoute), Some(&1));
+        assert_eq!(report.summary.get(&IssueKind::LengthMismatch), Some(&1));
+        assert_eq!(report.summary.get(&IssueKind::UnknownStopRoute), Some(&1));
+        assert_eq!(report.summary.get(&IssueKind::StopFarFromRoute), Some(&1));
+        assert!(report.to_markdown().contains("StopFarFromRoute"));
+    }
+
+    #[test]
+    fn test_parse_kilometers() {
+        assert_eq!(parse_kilometers("12,5"), Some(12.5));
+        assert_eq!(parse_kilometers("7.25 km"), Some(7.25));
+        assert_eq!(parse_kilometers("n/d"), None);
+    }
+}
@@ -0,0 +1,428 @@
+use chrono::{DateTime, Utc};
+use geo_types::Point;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, create_dir_all};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::db::{connect_to_db, DbError};
+use crate::utils::result_cache::CacheConfig;
+
+use super::{
+    data_loader::{DataLoader, LoaderError},
+    data_source::{DataSourceKind, PlannerDataSource},
+    postgis::PostgisDataSource,
+    snapshot::{PlannerSnapshot, SNAPSHOT_FILE},
+    geo_validation::{GeoValidator, ServiceAreaConfig},
+    index::{PlanningConfig, RoutePlanner},
+    manifest::DataManifest,
+    quality::{QualityConfig, QualityReport},
+    spatial_search::SpatialSearch,
+};
+
+/// Archivo de regiones usado si `REGIONS_CONFIG` no está definida.
+const DEFAULT_REGIONS_FILE: &str = "regions.toml";
+/// Reporte de calidad que se escribe en el directorio de cache al iniciar.
+const QUALITY_REPORT_FILE: &str = "quality_report.json";
+/// Región implícita cuando no hay archivo de regiones.
+const DEFAULT_REGION_ID: &str = "sv";
+
+#[derive(Debug, thiserror::Error)]
+pub enum RegionError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid regions config: {0}")]
+    Config(#[from] toml::de::Error),
+    #[error("Data loading error: {0}")]
+    Loader(#[from] LoaderError),
+    #[error("Database error: {0}")]
+    Database(#[from] DbError),
+    #[error("Unknown region: {0}")]
+    UnknownRegion(String),
+    #[error("No regions configured")]
+    NoRegions,
+}
+
+/// Configuración de una región: datos, límites y parámetros propios.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionConfig {
+    pub id: String,
+    pub name: Option<String>,
+    pub data_dir: PathBuf,
+    pub cache_dir: PathBuf,
+    #[serde(default)]
+    pub source: DataSourceKind,
+    /// Snapshot generado por `build_snapshot`; por defecto `<cache_dir>/planner.snapshot`.
+    pub snapshot: Option<PathBuf>,
+    #[serde(default)]
+    pub planning: PlanningConfig,
+    #[serde(default)]
+    pub service_area: ServiceAreaConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionsConfig {
+    pub default_region: Option<String>,
+    pub regions: Vec<RegionConfig>,
+}
+
+impl RegionConfig {
+    /// Carga los datos de la región y construye su planificador. Si hay un snapshot
+    /// válido se usa ese; si no, se leen las capas de la fuente configurada.
+    pub async fn build_planner(&self) -> Result<RoutePlanner, RegionError> {
+        let snapshot_path = self.snapshot_path();
+        if snapshot_path.exists() {
+            match PlannerSnapshot::load(&snapshot_path) {
+                Ok(snapshot) if snapshot.region != self.id => warn!(
+                    "Ignoring snapshot {}: built for region {}",
+                    snapshot_path.display(),
+                    snapshot.region
+                ),
+                Ok(snapshot) => {
+                    let dataset_version = self.dataset_version();
+                    if snapshot.dataset_version == dataset_version {
+                        return Ok(self.planner_from_snapshot(snapshot));
+                    }
+                    warn!(
+                        "Ignoring snapshot {}: built for dataset version {:?}, current is {:?}",
+                        snapshot_path.display(),
+                        snapshot.dataset_version,
+                        dataset_version
+                    );
+                }
+                Err(e) => warn!("Ignoring snapshot {}: {}", snapshot_path.display(), e),
+            }
+        }
+
+        let source = self.load_source().await?;
+        self.planner_from_source(source.as_ref())
+    }
+
+    /// Versión de los datos: la del manifiesto o, si no tiene, la fecha de
+    /// modificación más reciente de sus capas.
+    pub fn dataset_version(&self) -> Option<String> {
+        let manifest = DataManifest::for_data_dir(&self.data_dir).ok()?;
+        if manifest.version.is_some() {
+            return manifest.version;
+        }
+
+        manifest
+            .layers
+            .iter()
+            .filter_map(|layer| fs::metadata(self.data_dir.join(&layer.path)).ok()?.modified().ok())
+            .max()
+            .map(|modified| DateTime::<Utc>::from(modified).to_rfc3339())
+    }
+
+    pub fn snapshot_path(&self) -> PathBuf {
+        self.snapshot
+            .clone()
+            .unwrap_or_else(|| self.cache_dir.join(SNAPSHOT_FILE))
+    }
+
+    /// Lee las capas de la fuente configurada en `source`.
+    pub async fn load_source(&self) -> Result<Box<dyn PlannerDataSource>, RegionError> {
+        let mut data_loader = DataLoader::new(&self.data_dir);
+        match self.source {
+            DataSourceKind::Files => {
+                data_loader.load_all()?;
+                Ok(Box::new(data_loader))
+            }
+            DataSourceKind::Postgis => {
+                info!("Loading routes and stops for region {} from PostGIS", self.id);
+                // Sin base configurada se falla antes de leer los límites
+                let client = connect_to_db().await?;
+                data_loader.load_boundaries()?;
+                let source = PostgisDataSource::load(
+                    &client,
+                    data_loader.departments().clone(),
+                    data_loader.municipalities().clone(),
+                )
+                .await?;
+                Ok(Box::new(source))
+            }
+        }
+    }
+
+    pub fn planner_from_source(&self, source: &dyn PlannerDataSource) -> Result<RoutePlanner, RegionError> {
+        info!("Building planner for region {}", self.id);
+
+        if !self.cache_dir.exists() {
+            info!("Creating cache directory {}", self.cache_dir.display());
+            create_dir_all(&self.cache_dir)?;
+        }
+
+        self.write_quality_report(source);
+
+        let validator = self.validator_for(source);
+        let search = SpatialSearch::new(
+            source.routes().features.clone(),
+            source.bus_stops().features.iter()
+                .map(|feature| feature.properties.clone())
+                .collect(),
+            Some(self.cache_dir.clone()),
+        );
+
+        Ok(RoutePlanner::new(validator, search, Some(self.planning.clone())))
+    }
+
+    /// Usa el grafo de transbordos del snapshot en vez de recalcularlo.
+    pub fn planner_from_snapshot(&self, mut snapshot: PlannerSnapshot) -> RoutePlanner {
+        info!("Building planner for region {} from snapshot of {}", self.id, snapshot.created_at);
+        let validator = self.validator_for(&snapshot);
+        let search = snapshot.take_search(&self.cache_dir);
+        RoutePlanner::new(validator, search, Some(self.planning.clone()))
+    }
+
+    fn validator_for(&self, source: &dyn PlannerDataSource) -> GeoValidator {
+        let validator = GeoValidator::new(source.departments())
+            .with_municipalities(source.municipalities())
+            .with_service_area(self.service_area.clone());
+        info!("Loaded {} municipalities for region {}", validator.municipalities_count(), self.id);
+        validator
+    }
+
+    /// Revisa los datos cargados y deja el reporte junto al cache; no bloquea el arranque.
+    fn write_quality_report(&self, source: &dyn PlannerDataSource) {
+        let report = QualityReport::from_source(source, &QualityConfig::default());
+        if report.issues.is_empty() {
+            info!("Dataset quality check passed for region {}", self.id);
+        } else {
+            warn!(
+                "Dataset quality check for region {}: {} errors, {} warnings",
+                self.id,
+                report.error_count(),
+                report.warning_count()
+            );
+        }
+
+        let path = self.cache_dir.join(QUALITY_REPORT_FILE);
+        let written = serde_json::to_vec_pretty(&report)
+            .map_err(std::io::Error::from)
+            .and_then(|json| fs::write(&path, json));
+        if let Err(e) = written {
+            warn!("Could not write quality report to {}: {}", path.display(), e);
+        }
+    }
+}
+
+impl RegionsConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, RegionError> {
+        let content = fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// Una sola región con `./data` y `./cache`, el comportamiento histórico.
+    pub fn single() -> Self {
+        Self {
+            default_region: Some(DEFAULT_REGION_ID.to_string()),
+            regions: vec![RegionConfig {
+                id: DEFAULT_REGION_ID.to_string(),
+                name: Some("El Salvador".to_string()),
+                data_dir: PathBuf::from("./data"),
+                cache_dir: PathBuf::from("./cache"),
+                source: DataSourceKind::Files,
+                snapshot: None,
+                planning: PlanningConfig::default(),
+                service_area: ServiceAreaConfig::from_env(),
+            }],
+        }
+    }
+
+    /// Lee `REGIONS_CONFIG` o `regions.toml`; si ninguno existe usa [`RegionsConfig::single`].
+    pub fn from_env() -> Result<Self, RegionError> {
+        let path = env::var("REGIONS_CONFIG").unwrap_or_else(|_| DEFAULT_REGIONS_FILE.to_string());
+        if Path::new(&path).exists() {
+            info!("Loading regions from {}", path);
+            Self::load(path)
+        } else {
+            Ok(Self::single())
+        }
+    }
+
+    pub fn default_region_id(&self) -> Result<&str, RegionError> {
+        self.default_region
+            .as_deref()
+            .or_else(|| self.regions.first().map(|region| region.id.as_str()))
+            .ok_or(RegionError::NoRegions)
+    }
+}
+
+pub struct Region {
+    pub config: RegionConfig,
+    pub planner: Arc<RoutePlanner>,
+    pub dataset: DatasetInfo,
+}
+
+/// Origen y versión de los datos cargados en una región.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DatasetInfo {
+    pub version: Option<String>,
+    pub source: DataSourceKind,
+    #[schema(value_type = String, format = DateTime)]
+    pub loaded_at: DateTime<Utc>,
+}
+
+/// Planificadores cargados por región.
+pub struct PlannerRegistry {
+    regions: Vec<Region>,
+    default_region: String,
+}
+
+impl PlannerRegistry {
+    pub fn new(default_region: impl Into<String>) -> Self {
+        Self {
+            regions: Vec::new(),
+            default_region: default_region.into(),
+        }
+    }
+
+    /// Construye el planificador de cada región configurada.
+    pub async fn build(config: &RegionsConfig) -> Result<Self, RegionError> {
+        let mut registry = Self::new(config.default_region_id()?);
+        let plan_cache = CacheConfig::from_env("PLAN", 2048, 900);
+        for region in &config.regions {
+            let planner = region.build_planner().await?.with_cache(plan_cache.clone(), &format!("plans:{}", region.id));
+            registry.insert(region.clone(), planner);
+        }
+        Ok(registry)
+    }
+
+    pub fn insert(&mut self, config: RegionConfig, planner: RoutePlanner) {
+        self.regions.retain(|region| region.config.id != config.id);
+        let dataset = DatasetInfo {
+            version: config.dataset_version(),
+            source: config.source,
+            loaded_at: Utc::now(),
+        };
+        self.regions.push(Region {
+            config,
+            planner: Arc::new(planner),
+            dataset,
+        });
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Region> {
+        self.regions.iter().find(|region| region.config.id == id)
+    }
+
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    pub fn default_region(&self) -> &str {
+        &self.default_region
+    }
+
+    /// Selecciona la región pedida o, si no se indica, la que contiene el punto.
+    /// Si ninguna lo contiene se usa la región por defecto.
+    pub fn resolve(&self, requested: Option<&str>, point: Point<f64>) -> Result<&Region, RegionError> {
+        if let Some(id) = requested {
+            return self.get(id).ok_or_else(|| RegionError::UnknownRegion(id.to_string()));
+        }
+
+        self.regions
+            .iter()
+            .find(|region| region.planner.validator().contains(point))
+            .or_else(|| self.get(&self.default_region))
+            .or_else(|| self.regions.first())
+            .ok_or(RegionError::NoRegions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regions_config_parsing() {
+        let config: RegionsConfig = toml::from_str(r#"
+            default_region = "amss"
+
+            [[regions]]
+            id = "amss"
+            data_dir = "./data/amss"
+            cache_dir = "./cache/amss"
+
+            [[regions]]
+            id = "occidente"
+            name = "Santa Ana - Sonsonate"
+            data_dir = "./data/occidente"
+            cache_dir = "./cache/occidente"
+            source = "postgis"
+
+            [regions.planning]
+            max_transfers = 4
+
+            [regions.service_area]
+            buffer = 0.02
+        "#).unwrap();
+
+        assert_eq!(config.default_region_id().unwrap(), "amss");
+        assert_eq!(config.regions.len(), 2);
+        assert_eq!(config.regions[0].source, DataSourceKind::Files);
+        assert_eq!(config.regions[1].source, DataSourceKind::Postgis);
+        assert_eq!(config.regions[0].planning.max_transfers, PlanningConfig::default().max_transfers);
+        assert_eq!(config.regions[1].planning.max_transfers, 4);
+        assert_eq!(config.regions[1].planning.results_limit, PlanningConfig::default().results_limit);
+        assert_eq!(config.regions[1].service_area.buffer, 0.02);
+    }
+
+    #[test]
+    fn test_resolve_unknown_region() {
+        let registry = PlannerRegistry::new("amss");
+        assert!(matches!(
+            registry.resolve(Some("nope"), Point::new(-89.2, 13.7)),
+            Err(RegionError::UnknownRegion(_))
+        ));
+        assert!(matches!(
+            registry.resolve(None, Point::new(-89.2, 13.7)),
+            Err(RegionError::NoRegions)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_postgis_source_without_database_url() {
+        std::env::remove_var("DATABASE_URL");
+        let mut config = RegionsConfig::single().regions.remove(0);
+        config.source = DataSourceKind::Postgis;
+
+        assert!(matches!(
+            config.load_source().await,
+            Err(RegionError::Database(DbError::MissingUrl))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ignores_snapshot_of_other_dataset_version() {
+        std::env::remove_var("DATABASE_URL");
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = RegionsConfig::single().regions.remove(0);
+        config.source = DataSourceKind::Postgis;
+        config.data_dir = dir.path().to_path_buf();
+        config.cache_dir = dir.path().to_path_buf();
+        config.snapshot = Some(dir.path().join(SNAPSHOT_FILE));
+
+        // Sin manifiesto la versión actual es `None`; un snapshot de otra versión
+        // se descarta y se cae a la fuente, que falla por no tener base
+        let source = DataLoader::new(dir.path());
+        let search = SpatialSearch::without_cache(Vec::new(), Vec::new());
+        PlannerSnapshot::build(&config.id, Some("old".into()), &source, &search)
+            .unwrap()
+            .write(config.snapshot_path())
+            .unwrap();
+        assert!(matches!(
+            config.build_planner().await,
+            Err(RegionError::Database(DbError::MissingUrl))
+        ));
+
+        PlannerSnapshot::build(&config.id, config.dataset_version(), &source, &search)
+            .unwrap()
+            .write(config.snapshot_path())
+            .unwrap();
+        assert!(config.build_planner().await.is_ok());
+    }
+}
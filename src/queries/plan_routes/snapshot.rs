@@ -0,0 +1,526 @@
+use chrono::{DateTime, Utc};
+use geo::Rect;
+use log::info;
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use super::_structs::{
+    BusStopFeatureCollection, BusStopProperties, DepartmentFeatureCollection,
+    DepartmentProperties, GeoJsonFeature, GeoJsonFeatureCollection, GeoJsonGeometry,
+    MunicipalityFeatureCollection, MunicipalityProperties, RouteFeatureCollection, RouteProperties, TransferPoint,
+};
+use super::data_source::PlannerDataSource;
+use super::spatial_search::SpatialSearch;
+
+/// Nombre del snapshot dentro del directorio de cache de la región.
+pub const SNAPSHOT_FILE: &str = "planner.snapshot";
+/// Se incrementa con cualquier cambio en el formato o en los tipos serializados.
+pub const SNAPSHOT_VERSION: u32 = 4;
+const MAGIC: &[u8; 8] = b"GEOSNAP\0";
+const VERSION_END: usize = MAGIC.len() + 4;
+/// Magic, versión, 4 bytes de relleno y la cantidad de coordenadas: 24 bytes, para
+/// que la sección de coordenadas que sigue quede alineada a 8 dentro del mapa.
+const HEADER_LEN: usize = VERSION_END + 4 + 8;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Encoding error: {0}")]
+    Encoding(#[from] bincode::Error),
+    #[error("Not a planner snapshot")]
+    InvalidFormat,
+    #[error("Snapshot version {found} is not supported (expected {expected})")]
+    VersionMismatch { found: u32, expected: u32 },
+    #[error("Geometry mixes positions with different dimensions")]
+    MixedDimensions,
+}
+
+/// Tramo de la sección de coordenadas: `len` posiciones de `dims` valores desde `start`.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Span {
+    start: usize,
+    len: usize,
+    dims: usize,
+}
+
+impl Span {
+    fn end(&self) -> Option<usize> {
+        self.len.checked_mul(self.dims)?.checked_add(self.start)
+    }
+
+    fn positions(&self, coords: &[f64]) -> Vec<Vec<f64>> {
+        if self.dims == 0 {
+            return vec![Vec::new(); self.len];
+        }
+        coords[self.start..self.start + self.len * self.dims]
+            .chunks_exact(self.dims)
+            .map(<[f64]>::to_vec)
+            .collect()
+    }
+}
+
+// Las geometrías guardan solo tramos de la sección de coordenadas; bincode además
+// no admite enums con `#[serde(tag = "type")]` como `GeoJsonGeometry`.
+#[derive(Clone, Serialize, Deserialize)]
+enum Geometry {
+    Point(Span),
+    LineString(Span),
+    Polygon(Vec<Span>),
+    MultiPolygon(Vec<Vec<Span>>),
+    MultiLineString(Vec<Span>),
+}
+
+impl Geometry {
+    fn spans(&self) -> Box<dyn Iterator<Item = &Span> + '_> {
+        match self {
+            Geometry::Point(span) | Geometry::LineString(span) => Box::new(std::iter::once(span)),
+            Geometry::Polygon(spans) | Geometry::MultiLineString(spans) => Box::new(spans.iter()),
+            Geometry::MultiPolygon(polygons) => Box::new(polygons.iter().flatten()),
+        }
+    }
+
+    fn decode(&self, coords: &[f64]) -> GeoJsonGeometry {
+        let lines = |spans: &[Span]| spans.iter().map(|span| span.positions(coords)).collect();
+        match self {
+            Geometry::Point(span) => GeoJsonGeometry::Point {
+                coordinates: span.positions(coords).pop().unwrap_or_default(),
+            },
+            Geometry::LineString(span) => GeoJsonGeometry::LineString { coordinates: span.positions(coords) },
+            Geometry::Polygon(spans) => GeoJsonGeometry::Polygon { coordinates: lines(spans) },
+            Geometry::MultiPolygon(polygons) => GeoJsonGeometry::MultiPolygon {
+                coordinates: polygons.iter().map(|spans| lines(spans)).collect(),
+            },
+            Geometry::MultiLineString(spans) => GeoJsonGeometry::MultiLineString { coordinates: lines(spans) },
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Feature<T> {
+    properties: T,
+    geometry: Geometry,
+}
+
+impl<T> Feature<T> {
+    fn decode(self, coords: &[f64]) -> GeoJsonFeature<T> {
+        GeoJsonFeature {
+            r#type: "Feature".to_string(),
+            geometry: self.geometry.decode(coords),
+            properties: self.properties,
+        }
+    }
+}
+
+/// Variante de ruta tal como quedó indexada en `SpatialSearch`.
+#[derive(Serialize, Deserialize)]
+struct RouteEntry {
+    variant_id: String,
+    bounds: Option<Rect<f64>>,
+    feature: Feature<RouteProperties>,
+}
+
+/// Acumula las coordenadas de todas las geometrías en una sola sección plana.
+#[derive(Default)]
+struct Encoder {
+    coords: Vec<f64>,
+}
+
+impl Encoder {
+    fn span(&mut self, positions: &[Vec<f64>]) -> Result<Span, SnapshotError> {
+        let dims = positions.first().map_or(0, Vec::len);
+        let start = self.coords.len();
+        for position in positions {
+            if position.len() != dims {
+                return Err(SnapshotError::MixedDimensions);
+            }
+            self.coords.extend_from_slice(position);
+        }
+        Ok(Span { start, len: positions.len(), dims })
+    }
+
+    fn spans(&mut self, lines: &[Vec<Vec<f64>>]) -> Result<Vec<Span>, SnapshotError> {
+        lines.iter().map(|line| self.span(line)).collect()
+    }
+
+    fn geometry(&mut self, geometry: &GeoJsonGeometry) -> Result<Geometry, SnapshotError> {
+        Ok(match geometry {
+            GeoJsonGeometry::Point { coordinates } => {
+                Geometry::Point(self.span(std::slice::from_ref(coordinates))?)
+            }
+            GeoJsonGeometry::LineString { coordinates } => Geometry::LineString(self.span(coordinates)?),
+            GeoJsonGeometry::Polygon { coordinates } => Geometry::Polygon(self.spans(coordinates)?),
+            GeoJsonGeometry::MultiPolygon { coordinates } => Geometry::MultiPolygon(
+                coordinates
+                    .iter()
+                    .map(|polygon| self.spans(polygon))
+                    .collect::<Result<_, _>>()?,
+            ),
+            GeoJsonGeometry::MultiLineString { coordinates } => {
+                Geometry::MultiLineString(self.spans(coordinates)?)
+            }
+        })
+    }
+
+    fn feature<T: Clone>(&mut self, feature: &GeoJsonFeature<T>) -> Result<Feature<T>, SnapshotError> {
+        Ok(Feature {
+            properties: feature.properties.clone(),
+            geometry: self.geometry(&feature.geometry)?,
+        })
+    }
+
+    fn features<T: Clone>(&mut self, collection: &GeoJsonFeatureCollection<T>) -> Result<Vec<Feature<T>>, SnapshotError> {
+        collection.features.iter().map(|feature| self.feature(feature)).collect()
+    }
+}
+
+/// Todo lo que no son coordenadas; se decodifica completo al cargar.
+#[derive(Serialize, Deserialize)]
+struct SnapshotData {
+    departments: Vec<Feature<DepartmentProperties>>,
+    municipalities: Vec<Feature<MunicipalityProperties>>,
+    bus_stops: Vec<Feature<BusStopProperties>>,
+    routes: Vec<RouteEntry>,
+    unsupported_routes: usize,
+    intersections: HashMap<String, Vec<TransferPoint>>,
+}
+
+impl SnapshotData {
+    /// Si todos los tramos caen dentro de una sección de `len` coordenadas.
+    fn fits(&self, len: usize) -> bool {
+        let geometries = self.departments.iter().map(|feature| &feature.geometry)
+            .chain(self.municipalities.iter().map(|feature| &feature.geometry))
+            .chain(self.bus_stops.iter().map(|feature| &feature.geometry))
+            .chain(self.routes.iter().map(|route| &route.feature.geometry));
+        geometries
+            .flat_map(Geometry::spans)
+            .all(|span| span.end().is_some_and(|end| end <= len))
+    }
+}
+
+/// Sección de coordenadas: mapeada desde el archivo al cargar, en memoria al construir.
+enum Coordinates {
+    Mapped { map: Mmap, len: usize },
+    Owned(Vec<f64>),
+}
+
+impl Coordinates {
+    fn as_slice(&self) -> &[f64] {
+        match self {
+            // `load` ya comprobó tamaño y alineación, así que el cast no puede fallar.
+            Coordinates::Mapped { map, len } => bytemuck::cast_slice(&map[HEADER_LEN..HEADER_LEN + len * 8]),
+            Coordinates::Owned(coords) => coords,
+        }
+    }
+}
+
+/// Todo lo necesario para levantar el planificador de una región sin leer GeoJSON
+/// ni recalcular nada: límites, paradas, rutas ya indexadas (id de variante y caja)
+/// y grafo de transbordos.
+pub struct PlannerSnapshot {
+    pub region: String,
+    /// Versión de los datos con que se construyó (`RegionConfig::dataset_version`).
+    pub dataset_version: Option<String>,
+    pub created_at: DateTime<Utc>,
+    coordinates: Coordinates,
+    data: SnapshotData,
+    // Capas GeoJSON para `PlannerDataSource`; se arman solo si alguien las pide.
+    departments: OnceLock<DepartmentFeatureCollection>,
+    municipalities: OnceLock<MunicipalityFeatureCollection>,
+    bus_stops: OnceLock<BusStopFeatureCollection>,
+    routes: OnceLock<RouteFeatureCollection>,
+}
+
+fn layer<T: Clone>(name: &str, features: &[Feature<T>], coords: &[f64]) -> GeoJsonFeatureCollection<T> {
+    GeoJsonFeatureCollection::new(
+        name,
+        features.iter().cloned().map(|feature| feature.decode(coords)).collect(),
+    )
+}
+
+impl PlannerSnapshot {
+    /// Toma límites y paradas de `source`, y las rutas ya indexadas y el grafo de
+    /// transbordos de `search`.
+    pub fn build(
+        region: &str,
+        dataset_version: Option<String>,
+        source: &dyn PlannerDataSource,
+        search: &SpatialSearch,
+    ) -> Result<Self, SnapshotError> {
+        let mut encoder = Encoder::default();
+        let departments = encoder.features(source.departments())?;
+        let municipalities = encoder.features(source.municipalities())?;
+        let bus_stops = encoder.features(source.bus_stops())?;
+
+        let mut variants: Vec<_> = search.routes().collect();
+        variants.sort_unstable_by_key(|(id, _)| *id);
+        let routes = variants
+            .into_iter()
+            .map(|(id, route)| {
+                Ok(RouteEntry {
+                    variant_id: id.to_string(),
+                    bounds: search.route_bounds(id),
+                    feature: encoder.feature(route)?,
+                })
+            })
+            .collect::<Result<_, SnapshotError>>()?;
+
+        Ok(Self {
+            region: region.to_string(),
+            dataset_version,
+            created_at: Utc::now(),
+            coordinates: Coordinates::Owned(encoder.coords),
+            data: SnapshotData {
+                departments,
+                municipalities,
+                bus_stops,
+                routes,
+                unsupported_routes: search.unsupported_geometries(),
+                intersections: search.intersections().clone(),
+            },
+            departments: OnceLock::new(),
+            municipalities: OnceLock::new(),
+            bus_stops: OnceLock::new(),
+            routes: OnceLock::new(),
+        })
+    }
+
+    /// Escribe el snapshot a un archivo temporal y lo renombra, para no dejar
+    /// un snapshot a medias si el proceso se interrumpe.
+    ///
+    /// Formato: encabezado, coordenadas como `f64` little endian y al final el resto
+    /// en bincode.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let coords = self.coordinates.as_slice();
+        let tmp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+        writer.write_all(&[0; 4])?;
+        writer.write_all(&(coords.len() as u64).to_le_bytes())?;
+        for coord in coords {
+            writer.write_all(&coord.to_le_bytes())?;
+        }
+        bincode::serialize_into(
+            &mut writer,
+            &(&self.region, &self.dataset_version, &self.created_at, &self.data),
+        )?;
+        writer.flush()?;
+        drop(writer);
+
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Mapea el snapshot en memoria. Las coordenadas, que son casi todo el archivo,
+    /// se usan directamente desde el mapa sin leerlas ni decodificarlas; solo se
+    /// decodifica el resto (propiedades, ids de variante, cajas e intersecciones).
+    /// Cada geometría se copia una vez, al armar la búsqueda o la capa que se pida.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SnapshotError> {
+        let started = Instant::now();
+        let file = File::open(path.as_ref())?;
+        // SAFETY: el snapshot solo se reemplaza por rename (ver `write`), nunca se
+        // modifica en sitio, así que el contenido mapeado no cambia mientras se usa.
+        let map = unsafe { Mmap::map(&file)? };
+
+        if map.len() < VERSION_END || &map[..MAGIC.len()] != MAGIC {
+            return Err(SnapshotError::InvalidFormat);
+        }
+        let version = u32::from_le_bytes(map[MAGIC.len()..VERSION_END].try_into().unwrap());
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::VersionMismatch {
+                found: version,
+                expected: SNAPSHOT_VERSION,
+            });
+        }
+        if map.len() < HEADER_LEN {
+            return Err(SnapshotError::InvalidFormat);
+        }
+
+        let len = usize::try_from(u64::from_le_bytes(map[VERSION_END + 4..HEADER_LEN].try_into().unwrap()))
+            .map_err(|_| SnapshotError::InvalidFormat)?;
+        let coords_end = len
+            .checked_mul(8)
+            .and_then(|bytes| bytes.checked_add(HEADER_LEN))
+            .filter(|end| *end <= map.len())
+            .ok_or(SnapshotError::InvalidFormat)?;
+        // Las coordenadas están en little endian y se leen tal cual desde el mapa.
+        if cfg!(target_endian = "big") || bytemuck::try_cast_slice::<u8, f64>(&map[HEADER_LEN..coords_end]).is_err() {
+            return Err(SnapshotError::InvalidFormat);
+        }
+
+        let (region, dataset_version, created_at, data): (String, Option<String>, DateTime<Utc>, SnapshotData) =
+            bincode::deserialize(&map[coords_end..])?;
+        if !data.fits(len) {
+            return Err(SnapshotError::InvalidFormat);
+        }
+        info!(
+            "Loaded snapshot for region {} ({} routes, {} stops) in {} ms",
+            region,
+            data.routes.len(),
+            data.bus_stops.len(),
+            started.elapsed().as_millis()
+        );
+
+        Ok(Self {
+            region,
+            dataset_version,
+            created_at,
+            coordinates: Coordinates::Mapped { map, len },
+            data,
+            departments: OnceLock::new(),
+            municipalities: OnceLock::new(),
+            bus_stops: OnceLock::new(),
+            routes: OnceLock::new(),
+        })
+    }
+
+    /// Construye la búsqueda espacial consumiendo rutas e intersecciones, con los ids
+    /// de variante y cajas guardados en vez de recalcularlos.
+    pub fn take_search(&mut self, cache_dir: &Path) -> SpatialSearch {
+        let coords = self.coordinates.as_slice();
+        let routes = std::mem::take(&mut self.data.routes)
+            .into_iter()
+            .map(|route| (route.variant_id, route.feature.decode(coords), route.bounds))
+            .collect();
+        let mut search = SpatialSearch::from_index(
+            routes,
+            self.data.bus_stops.iter().map(|feature| feature.properties.clone()).collect(),
+            std::mem::take(&mut self.data.intersections),
+            self.data.unsupported_routes,
+            Some(cache_dir.to_path_buf()),
+        );
+        search.intersections_built_at = self.created_at;
+        search
+    }
+}
+
+impl PlannerDataSource for PlannerSnapshot {
+    fn departments(&self) -> &DepartmentFeatureCollection {
+        self.departments
+            .get_or_init(|| layer("departments", &self.data.departments, self.coordinates.as_slice()))
+    }
+
+    fn municipalities(&self) -> &MunicipalityFeatureCollection {
+        self.municipalities
+            .get_or_init(|| layer("municipalities", &self.data.municipalities, self.coordinates.as_slice()))
+    }
+
+    fn bus_stops(&self) -> &BusStopFeatureCollection {
+        self.bus_stops
+            .get_or_init(|| layer("stops", &self.data.bus_stops, self.coordinates.as_slice()))
+    }
+
+    /// Vacía después de `take_search`, salvo que ya se hubiera pedido antes.
+    fn routes(&self) -> &RouteFeatureCollection {
+        self.routes.get_or_init(|| {
+            let coords = self.coordinates.as_slice();
+            GeoJsonFeatureCollection::new(
+                "routes",
+                self.data.routes.iter().map(|route| route.feature.clone().decode(coords)).collect(),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan_routes::fixtures::{bus_stop_feature, collection, route};
+
+    struct Layers {
+        departments: DepartmentFeatureCollection,
+        municipalities: MunicipalityFeatureCollection,
+        bus_stops: BusStopFeatureCollection,
+        routes: RouteFeatureCollection,
+    }
+
+    impl PlannerDataSource for Layers {
+        fn departments(&self) -> &DepartmentFeatureCollection {
+            &self.departments
+        }
+
+        fn municipalities(&self) -> &MunicipalityFeatureCollection {
+            &self.municipalities
+        }
+
+        fn bus_stops(&self) -> &BusStopFeatureCollection {
+            &self.bus_stops
+        }
+
+        fn routes(&self) -> &RouteFeatureCollection {
+            &self.routes
+        }
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let layers = Layers {
+            departments: collection(vec![]),
+            municipalities: collection(vec![]),
+            bus_stops: collection(vec![bus_stop_feature("A", -89.2, 13.7)]),
+            routes: collection(vec![
+                route("A", vec![vec![-89.2, 13.7], vec![-89.1, 13.7]]),
+                route("B", vec![vec![-89.1, 13.7], vec![-89.1, 13.8]]),
+            ]),
+        };
+        let search = SpatialSearch::new(
+            layers.routes.features.clone(),
+            vec![],
+            Some(dir.path().to_path_buf()),
+        );
+
+        let path = dir.path().join(SNAPSHOT_FILE);
+        PlannerSnapshot::build("sv", Some("2024-05".into()), &layers, &search)
+            .unwrap()
+            .write(&path)
+            .unwrap();
+
+        let mut snapshot = PlannerSnapshot::load(&path).unwrap();
+        assert_eq!(snapshot.region, "sv");
+        assert_eq!(snapshot.dataset_version.as_deref(), Some("2024-05"));
+        assert_eq!(snapshot.routes().features, layers.routes.features);
+        assert_eq!(snapshot.bus_stops().features, layers.bus_stops.features);
+
+        let restored = snapshot.take_search(dir.path());
+        assert_eq!(restored.intersections(), search.intersections());
+        for (id, route) in search.routes() {
+            assert_eq!(restored.route(id), Some(route));
+            assert_eq!(restored.route_bounds(id), search.route_bounds(id));
+        }
+
+        // Un snapshot cortado no debe leerse fuera del archivo.
+        let bytes = fs::read(&path).unwrap();
+        let truncated = dir.path().join("truncated.snapshot");
+        fs::write(&truncated, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(PlannerSnapshot::load(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_rejects_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("not-a-snapshot");
+        fs::write(&path, b"{\"type\": \"FeatureCollection\"}").unwrap();
+        assert!(matches!(PlannerSnapshot::load(&path), Err(SnapshotError::InvalidFormat)));
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+        assert!(matches!(
+            PlannerSnapshot::load(&path),
+            Err(SnapshotError::VersionMismatch { .. })
+        ));
+    }
+}
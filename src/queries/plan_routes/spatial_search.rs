@@ -1,48 +1,59 @@
 use chrono::{DateTime, Utc};
 use geo::algorithm::euclidean_distance::EuclideanDistance;
-use geo::{LineString, Point};
-use log::{debug, error, info};
+use geo::{coord, LineString, Point, Rect};
+use log::{debug, error, info, warn};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fs::{create_dir_all, File};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::plan_routes::_structs::{
-    BusStopProperties, GeoJsonFeature, GeoJsonGeometry, RouteProperties, TransferPoint,
+    BusStopProperties, GeoJsonFeature, RouteProperties, TransferPoint,
     TransferType,
 };
 
+/// Versión del formato del cache; si no coincide se recalcula.
+const CACHE_VERSION: u32 = 4;
+
 // Estructura para el cache de intersecciones
+/// Archivo del grafo de transbordos dentro del directorio de cache.
+pub const INTERSECTIONS_CACHE_FILE: &str = "route_intersections.cache";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RouteIntersectionCache {
     version: u32,
+    /// Huella de las rutas con que se calculó (ver `routes_fingerprint`)
+    routes_fingerprint: u64,
     last_updated: DateTime<Utc>,
     intersections: HashMap<String, Vec<TransferPoint>>,
 }
 
 impl RouteIntersectionCache {
-    pub fn new(intersections: HashMap<String, Vec<TransferPoint>>) -> Self {
+    pub fn new(intersections: HashMap<String, Vec<TransferPoint>>, routes_fingerprint: u64) -> Self {
         Self {
-            version: 1,
+            version: CACHE_VERSION,
+            routes_fingerprint,
             last_updated: Utc::now(),
             intersections,
         }
     }
 
-    pub fn save_to_file(&self, cache_dir: &PathBuf) -> std::io::Result<()> {
+    pub fn save_to_file(&self, cache_dir: &Path) -> std::io::Result<()> {
         create_dir_all(cache_dir)?;
-        let cache_file = cache_dir.join("route_intersections.cache");
+        let cache_file = cache_dir.join(INTERSECTIONS_CACHE_FILE);
         let file = File::create(cache_file)?;
 
         bincode::serialize_into(file, self)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
 
         Ok(())
     }
 
-    pub fn load_from_file(cache_dir: &PathBuf) -> std::io::Result<Option<Self>> {
-        let cache_file = cache_dir.join("route_intersections.cache");
+    /// Lee el cache si es de esta versión y se calculó con las mismas rutas; si los
+    /// datos cambiaron, los ids de variante pueden apuntar a otras rutas.
+    pub fn load_from_file(cache_dir: &Path, routes_fingerprint: u64) -> std::io::Result<Option<Self>> {
+        let cache_file = cache_dir.join(INTERSECTIONS_CACHE_FILE);
 
         if !cache_file.exists() {
             return Ok(None);
@@ -50,7 +61,15 @@ impl RouteIntersectionCache {
 
         let file = File::open(cache_file)?;
 
-        match bincode::deserialize_from(file) {
+        match bincode::deserialize_from::<_, Self>(file) {
+            Ok(cache) if cache.version != CACHE_VERSION => {
+                info!("Ignoring cache version {} (expected {})", cache.version, CACHE_VERSION);
+                Ok(None)
+            }
+            Ok(cache) if cache.routes_fingerprint != routes_fingerprint => {
+                info!("Ignoring cache built for other route data");
+                Ok(None)
+            }
             Ok(cache) => Ok(Some(cache)),
             Err(e) => {
                 error!("Failed to load cache: {}", e);
@@ -76,11 +95,121 @@ pub enum SearchError {
     CacheError(String),
 }
 
+/// FNV-1a de 64 bits: estable entre versiones de Rust, a diferencia de `DefaultHasher`.
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+fn geometry_hash(route: &GeoJsonFeature<RouteProperties>) -> u64 {
+    route
+        .geometry
+        .line_coords()
+        .flatten()
+        .fold(FNV_OFFSET, |hash, value| fnv1a(hash, &value.to_le_bytes()))
+}
+
+/// Huella de las variantes cargadas (ids y geometrías), independiente del orden.
+fn routes_fingerprint(routes: &HashMap<String, GeoJsonFeature<RouteProperties>>) -> u64 {
+    let mut ids: Vec<&String> = routes.keys().collect();
+    ids.sort();
+    ids.into_iter().fold(FNV_OFFSET, |hash, id| {
+        let hash = fnv1a(hash, id.as_bytes());
+        fnv1a(hash, &geometry_hash(&routes[id]).to_le_bytes())
+    })
+}
+
+/// Identificador estable de cada variante de ruta: `CÓDIGO/SENTIDO`. Si varias
+/// variantes comparten código y sentido, se distinguen con `#` y un hash de su
+/// geometría, así que no dependen del orden de carga; las de geometría idéntica
+/// llevan además `-n`. Las rutas sin código usan `SIN_CODIGO`.
+pub fn route_variant_ids(routes: &[GeoJsonFeature<RouteProperties>]) -> Vec<String> {
+    let bases: Vec<String> = routes
+        .iter()
+        .map(|route| {
+            format!(
+                "{}/{}",
+                route.properties.codigo_de.as_deref().unwrap_or("SIN_CODIGO"),
+                route.properties.sentido.as_deref().unwrap_or("-")
+            )
+        })
+        .collect();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for base in &bases {
+        *counts.entry(base).or_insert(0) += 1;
+    }
+
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    bases
+        .iter()
+        .zip(routes)
+        .map(|(base, route)| {
+            if counts[base.as_str()] == 1 {
+                return base.clone();
+            }
+            let id = format!("{}#{:08x}", base, geometry_hash(route) as u32);
+            let count = seen.entry(id.clone()).or_insert(0);
+            *count += 1;
+            if *count == 1 {
+                id
+            } else {
+                format!("{}-{}", id, count)
+            }
+        })
+        .collect()
+}
+
+/// Variante de ruta con su id.
+type RouteRef<'a> = (&'a str, &'a GeoJsonFeature<RouteProperties>);
+
+/// Si dos variantes pertenecen a la misma ruta pública.
+fn same_route_code(a: &GeoJsonFeature<RouteProperties>, b: &GeoJsonFeature<RouteProperties>) -> bool {
+    a.properties.codigo_de.is_some() && a.properties.codigo_de == b.properties.codigo_de
+}
+
 pub struct SpatialSearch {
     bus_stops: HashMap<String, Vec<BusStopProperties>>,
+    // Por id de variante (ver `route_variant_ids`)
     routes: HashMap<String, GeoJsonFeature<RouteProperties>>,
+    /// Caja lon/lat de cada variante, para descartar rutas sin recorrer su geometría
+    route_bounds: HashMap<String, Rect<f64>>,
     route_intersections: HashMap<String, Vec<TransferPoint>>,
     cache_dir: PathBuf,
+    unsupported_geometries: usize,
+    /// Cuándo se calcularon las intersecciones (cache, snapshot o al cargar)
+    pub(super) intersections_built_at: DateTime<Utc>,
+}
+
+/// Agrupa las paradas por el código de ruta que atienden.
+fn stops_by_route(bus_stops: Vec<BusStopProperties>) -> HashMap<String, Vec<BusStopProperties>> {
+    bus_stops.into_iter().fold(HashMap::new(), |mut acc, stop| {
+        if let Some(ruta) = stop.ruta.clone() {
+            acc.entry(ruta).or_default().push(stop);
+        }
+        acc
+    })
+}
+
+/// Caja lon/lat de una ruta; `None` si no tiene coordenadas.
+fn line_bounds(route: &GeoJsonFeature<RouteProperties>) -> Option<Rect<f64>> {
+    let mut coords = route
+        .geometry
+        .line_coords()
+        .filter(|coord| coord.len() >= 2)
+        .map(|coord| coord! { x: coord[0], y: coord[1] });
+    let first = coords.next()?;
+    let (min, max) = coords.fold((first, first), |(min, max), coord| {
+        (
+            coord! { x: min.x.min(coord.x), y: min.y.min(coord.y) },
+            coord! { x: max.x.max(coord.x), y: max.y.max(coord.y) },
+        )
+    });
+    Some(Rect::new(min, max))
 }
 
 impl SpatialSearch {
@@ -89,38 +218,14 @@ impl SpatialSearch {
         bus_stops: Vec<BusStopProperties>,
         cache_dir: Option<PathBuf>,
     ) -> Self {
-        let bus_stops_map = bus_stops.into_iter().fold(HashMap::<String, Vec<BusStopProperties>>::new(), |mut acc, stop| {
-            if let Some(ruta) = stop.ruta.clone() {
-                acc.entry(ruta).or_default().push(stop);
-            }
-            acc
-        });
-
-        let routes_map = routes
-            .into_iter()
-            .filter_map(|route| {
-                route
-                    .properties
-                    .codigo_de
-                    .clone()
-                    .map(|codigo_de| (codigo_de, route))
-            })
-            .collect();
-
-        let cache_dir = cache_dir.unwrap_or_else(|| PathBuf::from("./cache"));
-
-        let mut search = Self {
-            bus_stops: bus_stops_map,
-            routes: routes_map,
-            route_intersections: HashMap::new(),
-            cache_dir,
-        };
+        let mut search = Self::index(routes, bus_stops, cache_dir);
 
         // Intentar cargar del cache
         match search.load_intersections_cache() {
             Ok(Some(cache)) => {
                 info!("Loaded route intersections from cache");
                 search.route_intersections = cache.intersections;
+                search.intersections_built_at = cache.last_updated;
             }
             Ok(None) => {
                 info!("Cache not found, precalculating intersections");
@@ -138,13 +243,157 @@ impl SpatialSearch {
         search
     }
 
+    /// Construye la búsqueda con intersecciones ya calculadas (p. ej. de un snapshot),
+    /// sin leer ni escribir el cache.
+    pub fn with_intersections(
+        routes: Vec<GeoJsonFeature<RouteProperties>>,
+        bus_stops: Vec<BusStopProperties>,
+        route_intersections: HashMap<String, Vec<TransferPoint>>,
+        cache_dir: Option<PathBuf>,
+    ) -> Self {
+        let mut search = Self::index(routes, bus_stops, cache_dir);
+        search.route_intersections = route_intersections;
+        search
+    }
+
+    /// Recalcula todas las intersecciones sin leer ni escribir el cache.
+    pub fn without_cache(
+        routes: Vec<GeoJsonFeature<RouteProperties>>,
+        bus_stops: Vec<BusStopProperties>,
+    ) -> Self {
+        let mut search = Self::index(routes, bus_stops, None);
+        search.precalculate_intersections();
+        search
+    }
+
+    /// Reconstruye la búsqueda con ids de variante, cajas e intersecciones ya
+    /// calculados (p. ej. de un snapshot), sin recorrer geometrías ni tocar el cache.
+    pub fn from_index(
+        routes: Vec<(String, GeoJsonFeature<RouteProperties>, Option<Rect<f64>>)>,
+        bus_stops: Vec<BusStopProperties>,
+        route_intersections: HashMap<String, Vec<TransferPoint>>,
+        unsupported_geometries: usize,
+        cache_dir: Option<PathBuf>,
+    ) -> Self {
+        let mut route_bounds = HashMap::with_capacity(routes.len());
+        let mut routes_map = HashMap::with_capacity(routes.len());
+        for (id, route, bounds) in routes {
+            if let Some(bounds) = bounds {
+                route_bounds.insert(id.clone(), bounds);
+            }
+            routes_map.insert(id, route);
+        }
+
+        Self {
+            bus_stops: stops_by_route(bus_stops),
+            routes: routes_map,
+            route_bounds,
+            route_intersections,
+            cache_dir: cache_dir.unwrap_or_else(|| PathBuf::from("./cache")),
+            unsupported_geometries,
+            intersections_built_at: Utc::now(),
+        }
+    }
+
+    fn index(
+        routes: Vec<GeoJsonFeature<RouteProperties>>,
+        bus_stops: Vec<BusStopProperties>,
+        cache_dir: Option<PathBuf>,
+    ) -> Self {
+        let bus_stops_map = stops_by_route(bus_stops);
+
+        let total_routes = routes.len();
+        let routes: Vec<_> = routes
+            .into_iter()
+            .filter(|route| route.geometry.is_linear())
+            .collect();
+        let unsupported_geometries = total_routes - routes.len();
+        if unsupported_geometries > 0 {
+            warn!(
+                "Skipping {} of {} routes with unsupported geometry (expected LineString or MultiLineString)",
+                unsupported_geometries, total_routes
+            );
+        }
+
+        let routes_map: HashMap<String, GeoJsonFeature<RouteProperties>> = route_variant_ids(&routes)
+            .into_iter()
+            .zip(routes)
+            .collect();
+        let route_bounds = routes_map
+            .iter()
+            .filter_map(|(id, route)| Some((id.clone(), line_bounds(route)?)))
+            .collect();
+
+        let cache_dir = cache_dir.unwrap_or_else(|| PathBuf::from("./cache"));
+
+        Self {
+            bus_stops: bus_stops_map,
+            routes: routes_map,
+            route_bounds,
+            route_intersections: HashMap::new(),
+            cache_dir,
+            unsupported_geometries,
+            intersections_built_at: Utc::now(),
+        }
+    }
+
+    /// Grafo de transbordos entre variantes.
+    pub fn intersections(&self) -> &HashMap<String, Vec<TransferPoint>> {
+        &self.route_intersections
+    }
+
+    pub fn intersections_built_at(&self) -> DateTime<Utc> {
+        self.intersections_built_at
+    }
+
+    pub fn intersections_cache_path(&self) -> PathBuf {
+        self.cache_dir.join(INTERSECTIONS_CACHE_FILE)
+    }
+
+    /// Variantes de ruta cargadas.
+    pub fn route_count(&self) -> usize {
+        self.routes.len()
+    }
+
+    /// Paradas asociadas a alguna ruta.
+    pub fn stop_count(&self) -> usize {
+        self.bus_stops.values().map(Vec::len).sum()
+    }
+
+    /// Variantes cargadas con su id, en orden arbitrario.
+    pub fn routes(&self) -> impl Iterator<Item = (&str, &GeoJsonFeature<RouteProperties>)> {
+        self.routes.iter().map(|(id, route)| (id.as_str(), route))
+    }
+
+    /// Variantes cuya caja lon/lat toca `bounds`, en orden arbitrario.
+    pub fn routes_within(&self, bounds: Rect<f64>) -> impl Iterator<Item = (&str, &GeoJsonFeature<RouteProperties>)> {
+        self.routes().filter(move |(id, _)| {
+            self.route_bounds.get(*id).is_some_and(|route| {
+                route.min().x <= bounds.max().x
+                    && route.max().x >= bounds.min().x
+                    && route.min().y <= bounds.max().y
+                    && route.max().y >= bounds.min().y
+            })
+        })
+    }
+
+    /// Paradas asociadas a alguna ruta; una parada compartida aparece una vez por ruta.
+    pub fn bus_stops(&self) -> impl Iterator<Item = &BusStopProperties> {
+        self.bus_stops.values().flatten()
+    }
+
+    /// Rutas descartadas al cargar por no tener geometría lineal.
+    pub fn unsupported_geometries(&self) -> usize {
+        self.unsupported_geometries
+    }
+
     fn load_intersections_cache(&self) -> Result<Option<RouteIntersectionCache>, SearchError> {
-        RouteIntersectionCache::load_from_file(&self.cache_dir)
+        RouteIntersectionCache::load_from_file(&self.cache_dir, routes_fingerprint(&self.routes))
             .map_err(|e| SearchError::CacheError(e.to_string()))
     }
 
     fn save_intersections_cache(&self) -> Result<(), SearchError> {
-        let cache = RouteIntersectionCache::new(self.route_intersections.clone());
+        let cache = RouteIntersectionCache::new(self.route_intersections.clone(), routes_fingerprint(&self.routes));
         cache
             .save_to_file(&self.cache_dir)
             .map_err(|e| SearchError::CacheError(e.to_string()))
@@ -173,12 +422,12 @@ impl SpatialSearch {
                     let potential_intersections = self.find_potential_intersections(route1);
 
                     for other_code in potential_intersections {
-                        if route_code == &other_code {
+                        let route2 = &self.routes[&other_code];
+                        if route_code == &other_code || same_route_code(route1, route2) {
                             continue;
                         }
 
-                        let route2 = &self.routes[&other_code];
-                        if let Some(transfer) = self.find_best_transfer(route1, route2) {
+                        if let Some(transfer) = self.find_best_transfer(route_code, route1, &other_code, route2) {
                             route_intersections.push(transfer);
                         }
                     }
@@ -201,24 +450,23 @@ impl SpatialSearch {
 
     fn find_potential_intersections(&self, route: &GeoJsonFeature<RouteProperties>) -> Vec<String> {
         // Calcular bounding box de la ruta
-        let bbox = match &route.geometry {
-            GeoJsonGeometry::LineString { coordinates } => {
-                let mut min_x = f64::MAX;
-                let mut min_y = f64::MAX;
-                let mut max_x = f64::MIN;
-                let mut max_y = f64::MIN;
-
-                for coord in coordinates {
-                    min_x = min_x.min(coord[0]);
-                    min_y = min_y.min(coord[1]);
-                    max_x = max_x.max(coord[0]);
-                    max_y = max_y.max(coord[1]);
-                }
+        if !route.geometry.is_linear() {
+            return vec![];
+        }
 
-                (min_x, min_y, max_x, max_y)
-            }
-            _ => return vec![],
-        };
+        let mut min_x = f64::MAX;
+        let mut min_y = f64::MAX;
+        let mut max_x = f64::MIN;
+        let mut max_y = f64::MIN;
+
+        for coord in route.geometry.line_coords() {
+            min_x = min_x.min(coord[0]);
+            min_y = min_y.min(coord[1]);
+            max_x = max_x.max(coord[0]);
+            max_y = max_y.max(coord[1]);
+        }
+
+        let bbox = (min_x, min_y, max_x, max_y);
 
         // Expandir el bounding box un poco para considerar rutas cercanas (≈1km)
         let (min_x, min_y, max_x, max_y) =
@@ -227,19 +475,15 @@ impl SpatialSearch {
         self.routes
             .iter()
             .filter_map(|(code, other_route)| {
-                if let GeoJsonGeometry::LineString { coordinates } = &other_route.geometry {
-                    let intersects = coordinates.iter().any(|coord| {
-                        coord[0] >= min_x
-                            && coord[0] <= max_x
-                            && coord[1] >= min_y
-                            && coord[1] <= max_y
-                    });
-
-                    if intersects {
-                        Some(code.clone())
-                    } else {
-                        None
-                    }
+                let intersects = other_route.geometry.line_coords().any(|coord| {
+                    coord[0] >= min_x
+                        && coord[0] <= max_x
+                        && coord[1] >= min_y
+                        && coord[1] <= max_y
+                });
+
+                if intersects {
+                    Some(code.clone())
                 } else {
                     None
                 }
@@ -249,21 +493,28 @@ impl SpatialSearch {
 
     fn find_best_transfer(
         &self,
+        from: &str,
         route1: &GeoJsonFeature<RouteProperties>,
+        to: &str,
         route2: &GeoJsonFeature<RouteProperties>,
     ) -> Option<TransferPoint> {
-        // Primero intentar encontrar una parada directa
-        if let Some(transfer) = self.find_direct_transfer(route1, route2) {
-            return Some(transfer);
-        }
-
-        // Luego buscar paradas cercanas (≤500m)
-        if let Some(transfer) = self.find_near_transfer(route1, route2, 0.005) {
-            return Some(transfer);
-        }
+        // Primero intentar encontrar una parada directa,
+        // luego paradas cercanas (≤500m) y finalmente puntos próximos (≤1km)
+        let transfer = self
+            .find_direct_transfer(route1, route2)
+            .or_else(|| self.find_near_transfer(route1, route2, 0.005))
+            .or_else(|| self.find_proximate_transfer(route1, route2, 0.01))?;
+
+        // Las transferencias apuntan a variantes, no a códigos públicos
+        Some(TransferPoint {
+            from_route: from.to_string(),
+            to_route: to.to_string(),
+            ..transfer
+        })
+    }
 
-        // Finalmente buscar puntos próximos (≤1km)
-        self.find_proximate_transfer(route1, route2, 0.01)
+    fn route_stops(&self, route: &GeoJsonFeature<RouteProperties>) -> Option<&Vec<BusStopProperties>> {
+        route.properties.codigo_de.as_ref().and_then(|code| self.bus_stops.get(code))
     }
 
     fn find_direct_transfer(
@@ -271,12 +522,7 @@ impl SpatialSearch {
         route1: &GeoJsonFeature<RouteProperties>,
         route2: &GeoJsonFeature<RouteProperties>,
     ) -> Option<TransferPoint> {
-        if let (Some(stops1), Some(stops2)) = (
-            self.bus_stops
-                .get(route1.properties.codigo_de.as_ref().unwrap()),
-            self.bus_stops
-                .get(route2.properties.codigo_de.as_ref().unwrap()),
-        ) {
+        if let (Some(stops1), Some(stops2)) = (self.route_stops(route1), self.route_stops(route2)) {
             for stop1 in stops1 {
                 for stop2 in stops2 {
                     if stop1.latitud == stop2.latitud && stop1.longitud == stop2.longitud {
@@ -301,12 +547,7 @@ impl SpatialSearch {
         route2: &GeoJsonFeature<RouteProperties>,
         max_distance: f64,
     ) -> Option<TransferPoint> {
-        if let (Some(stops1), Some(stops2)) = (
-            self.bus_stops
-                .get(route1.properties.codigo_de.as_ref().unwrap()),
-            self.bus_stops
-                .get(route2.properties.codigo_de.as_ref().unwrap()),
-        ) {
+        if let (Some(stops1), Some(stops2)) = (self.route_stops(route1), self.route_stops(route2)) {
             let mut best_transfer = None;
             let mut min_distance = max_distance;
 
@@ -348,33 +589,23 @@ impl SpatialSearch {
         let mut best_transfer = None;
         let mut min_distance = max_distance;
 
-        if let (
-            GeoJsonGeometry::LineString {
-                coordinates: coords1,
-            },
-            GeoJsonGeometry::LineString {
-                coordinates: coords2,
-            },
-        ) = (&route1.geometry, &route2.geometry)
-        {
-            for coord1 in coords1 {
-                let point1 = Point::new(coord1[0], coord1[1]);
-
-                for coord2 in coords2 {
-                    let point2 = Point::new(coord2[0], coord2[1]);
-                    let distance = point1.euclidean_distance(&point2);
-
-                    if distance < min_distance {
-                        min_distance = distance;
-                        best_transfer = Some(TransferPoint {
-                            location: point1,
-                            bus_stop: None,
-                            distance_to_route: distance,
-                            transfer_type: TransferType::Proximate,
-                            from_route: route1.properties.codigo_de.clone().unwrap_or_default(),
-                            to_route: route2.properties.codigo_de.clone().unwrap_or_default(),
-                        });
-                    }
+        for coord1 in route1.geometry.line_coords() {
+            let point1 = Point::new(coord1[0], coord1[1]);
+
+            for coord2 in route2.geometry.line_coords() {
+                let point2 = Point::new(coord2[0], coord2[1]);
+                let distance = point1.euclidean_distance(&point2);
+
+                if distance < min_distance {
+                    min_distance = distance;
+                    best_transfer = Some(TransferPoint {
+                        location: point1,
+                        bus_stop: None,
+                        distance_to_route: distance,
+                        transfer_type: TransferType::Proximate,
+                        from_route: route1.properties.codigo_de.clone().unwrap_or_default(),
+                        to_route: route2.properties.codigo_de.clone().unwrap_or_default(),
+                    });
                 }
             }
         }
@@ -425,45 +656,62 @@ impl SpatialSearch {
                 .unwrap_or(std::cmp::Ordering::Equal)
         });
 
+        // Variantes de las mismas rutas públicas se agrupan: queda la mejor
+        let mut seen_codes = HashSet::new();
+        route_plans.retain(|plan| seen_codes.insert(plan.route_codes()));
+
         Ok(route_plans.into_iter().take(3).collect())
     }
 
-    fn find_nearby_routes(
-        &self,
-        point: Point<f64>,
-        max_distance: f64,
-    ) -> Vec<&GeoJsonFeature<RouteProperties>> {
+    /// Ids de las variantes con algún vértice a `max_distance` o menos del punto, ordenados.
+    pub fn nearby_route_ids(&self, point: Point<f64>, max_distance: f64) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .find_nearby_routes(point, max_distance)
+            .into_iter()
+            .map(|(id, _)| id.to_string())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn route(&self, variant_id: &str) -> Option<&GeoJsonFeature<RouteProperties>> {
+        self.routes.get(variant_id)
+    }
+
+    /// Caja lon/lat de una variante, si tiene coordenadas.
+    pub fn route_bounds(&self, variant_id: &str) -> Option<Rect<f64>> {
+        self.route_bounds.get(variant_id).copied()
+    }
+
+    fn find_nearby_routes(&self, point: Point<f64>, max_distance: f64) -> Vec<RouteRef<'_>> {
         self.routes
-            .values()
+            .iter()
+            .map(|(id, route)| (id.as_str(), route))
             .par_bridge()
-            .filter(|route| {
-                if let GeoJsonGeometry::LineString { coordinates } = &route.geometry {
-                    coordinates.iter().any(|coord| {
-                        let route_point = Point::new(coord[0], coord[1]);
-                        route_point.euclidean_distance(&point) <= max_distance
-                    })
-                } else {
-                    false
-                }
+            .filter(|(_, route)| {
+                route.geometry.line_coords().any(|coord| {
+                    let route_point = Point::new(coord[0], coord[1]);
+                    route_point.euclidean_distance(&point) <= max_distance
+                })
             })
             .collect()
     }
 
     fn find_all_possible_routes(
         &self,
-        origin_routes: &[&GeoJsonFeature<RouteProperties>],
-        destination_routes: &[&GeoJsonFeature<RouteProperties>],
-        origin: Point<f64>,
+        origin_routes: &[RouteRef<'_>],
+        destination_routes: &[RouteRef<'_>],
+        _origin: Point<f64>,
         destination: Point<f64>,
         max_transfers: i32,
     ) -> Result<Vec<crate::plan_routes::_structs::RoutePlan>, SearchError> {
         let mut plans = Vec::new();
         let mut visited = HashSet::new();
 
-        for start_route in origin_routes {
+        for &start_route in origin_routes {
             let mut current_plan = crate::plan_routes::_structs::RoutePlan::new();
             visited.clear();
-            visited.insert(start_route.properties.codigo_de.clone().unwrap_or_default());
+            visited.insert(start_route.0.to_string());
 
             self.explore_route_path(
                 start_route,
@@ -483,10 +731,11 @@ impl SpatialSearch {
         Ok(plans)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn explore_route_path(
         &self,
-        current_route: &GeoJsonFeature<RouteProperties>,
-        destination_routes: &[&GeoJsonFeature<RouteProperties>],
+        (current_id, current_route): RouteRef<'_>,
+        destination_routes: &[RouteRef<'_>],
         destination: Point<f64>,
         transfers_left: i32,
         visited: &mut HashSet<String>,
@@ -494,9 +743,10 @@ impl SpatialSearch {
         all_plans: &mut Vec<crate::plan_routes::_structs::RoutePlan>,
     ) {
         // Si llegamos a una ruta de destino, agregar el plan
-        if destination_routes.contains(&current_route) {
+        if destination_routes.iter().any(|(id, _)| *id == current_id) {
             if let Some(end_point) = self.find_closest_point_on_route(current_route, destination) {
                 let segment = crate::plan_routes::_structs::RouteSegment {
+                    variant_id: current_id.to_string(),
                     route: current_route.properties.clone(),
                     transfer_type: TransferType::Direct,
                     transfer_point: TransferPoint {
@@ -504,11 +754,7 @@ impl SpatialSearch {
                         bus_stop: None,
                         distance_to_route: end_point.euclidean_distance(&destination),
                         transfer_type: TransferType::Direct,
-                        from_route: current_route
-                            .properties
-                            .codigo_de
-                            .clone()
-                            .unwrap_or_default(),
+                        from_route: current_id.to_string(),
                         to_route: String::new(),
                     },
                     segment_distance: self
@@ -530,14 +776,15 @@ impl SpatialSearch {
         // Explorar las intersecciones con otras rutas
         if let Some(transfers) = self
             .route_intersections
-            .get(current_route.properties.codigo_de.as_ref().unwrap())
+            .get(current_id)
         {
             for transfer in transfers {
-                if let Some(next_route) = self.routes.get(&transfer.to_route) {
-                    if !visited.contains(next_route.properties.codigo_de.as_ref().unwrap()) {
-                        visited.insert(next_route.properties.codigo_de.clone().unwrap_or_default());
+                if let Some((next_id, next_route)) = self.routes.get_key_value(&transfer.to_route) {
+                    if !visited.contains(next_id) {
+                        visited.insert(next_id.clone());
 
                         let segment = crate::plan_routes::_structs::RouteSegment {
+                            variant_id: current_id.to_string(),
                             route: current_route.properties.clone(),
                             transfer_point: transfer.clone(),
                             transfer_type: transfer.transfer_type.clone(),
@@ -549,7 +796,7 @@ impl SpatialSearch {
                         current_plan.add_segment(segment);
 
                         self.explore_route_path(
-                            next_route,
+                            (next_id, next_route),
                             destination_routes,
                             destination,
                             transfers_left - 1,
@@ -558,7 +805,7 @@ impl SpatialSearch {
                             all_plans,
                         );
 
-                        visited.remove(next_route.properties.codigo_de.as_ref().unwrap());
+                        visited.remove(next_id);
                         current_plan.routes.pop();
                     }
                 }
@@ -571,20 +818,17 @@ impl SpatialSearch {
         route: &GeoJsonFeature<RouteProperties>,
         point: Point<f64>,
     ) -> Option<Point<f64>> {
-        if let GeoJsonGeometry::LineString { coordinates } = &route.geometry {
-            coordinates
-                .iter()
-                .min_by(|a, b| {
-                    let pa = Point::new(a[0], a[1]);
-                    let pb = Point::new(b[0], b[1]);
-                    pa.euclidean_distance(&point)
-                        .partial_cmp(&pb.euclidean_distance(&point))
-                        .unwrap_or(std::cmp::Ordering::Equal)
-                })
-                .map(|coord| Point::new(coord[0], coord[1]))
-        } else {
-            None
-        }
+        route
+            .geometry
+            .line_coords()
+            .min_by(|a, b| {
+                let pa = Point::new(a[0], a[1]);
+                let pb = Point::new(b[0], b[1]);
+                pa.euclidean_distance(&point)
+                    .partial_cmp(&pb.euclidean_distance(&point))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|coord| Point::new(coord[0], coord[1]))
     }
 
     fn calculate_route_distance(
@@ -592,31 +836,154 @@ impl SpatialSearch {
         route: &GeoJsonFeature<RouteProperties>,
         point: Point<f64>,
     ) -> Option<f64> {
-        if let GeoJsonGeometry::LineString { coordinates } = &route.geometry {
-            let line: Vec<(f64, f64)> = coordinates
-                .iter()
-                .map(|coord| (coord[0], coord[1]))
-                .collect();
-            let linestring = LineString::from(line);
-            Some(linestring.euclidean_distance(&point))
-        } else {
-            None
-        }
+        // En rutas de varios tramos se usa el tramo más cercano
+        route
+            .geometry
+            .line_parts()
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                let line: Vec<(f64, f64)> = part
+                    .iter()
+                    .map(|coord| (coord[0], coord[1]))
+                    .collect();
+                LineString::from(line).euclidean_distance(&point)
+            })
+            .min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::plan_routes::_structs::GeoJsonGeometry;
+    use crate::plan_routes::fixtures::{route, variant};
+
+    fn search(routes: Vec<GeoJsonFeature<RouteProperties>>) -> (SpatialSearch, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let search = SpatialSearch::new(routes, vec![], Some(dir.path().to_path_buf()));
+        (search, dir)
+    }
 
-    // TODO: Implementar pruebas unitarias
     #[test]
     fn test_find_nearby_routes() {
-        // Implementar test
+        let (search, _dir) = search(vec![
+            variant(Some("MULTI"), None, GeoJsonGeometry::MultiLineString {
+                coordinates: vec![
+                    vec![vec![-89.30, 13.70], vec![-89.25, 13.70]],
+                    vec![vec![-89.10, 13.70], vec![-89.05, 13.70]],
+                ],
+            }),
+            variant(Some("PUNTO"), None, GeoJsonGeometry::Point { coordinates: vec![-89.10, 13.70] }),
+        ]);
+
+        assert_eq!(search.unsupported_geometries(), 1);
+
+        // Cerca del segundo tramo
+        let nearby = search.find_nearby_routes(Point::new(-89.055, 13.701), 0.01);
+        assert_eq!(nearby.len(), 1);
+        let distance = search.calculate_route_distance(nearby[0].1, Point::new(-89.055, 13.701)).unwrap();
+        assert!((distance - 0.001).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_routes_within_bounds() {
+        let (search, _dir) = search(vec![
+            route("A", vec![vec![-89.3, 13.6], vec![-89.2, 13.7]]),
+            route("B", vec![vec![-88.3, 13.4], vec![-88.2, 13.5]]),
+        ]);
+        let bounds = Rect::new(coord! { x: -89.25, y: 13.65 }, coord! { x: -89.0, y: 13.8 });
+        let found: Vec<_> = search
+            .routes_within(bounds)
+            .filter_map(|(_, route)| route.properties.codigo_de.as_deref())
+            .collect();
+        assert_eq!(found, ["A"]);
     }
 
     #[test]
     fn test_route_finding() {
-        // Implementar test
+        let (search, _dir) = search(vec![
+            variant(Some("A"), None, GeoJsonGeometry::MultiLineString {
+                coordinates: vec![
+                    vec![vec![-89.30, 13.70], vec![-89.25, 13.70]],
+                    vec![vec![-89.25, 13.70], vec![-89.20, 13.70]],
+                ],
+            }),
+            route("B", vec![vec![-89.20, 13.70], vec![-89.20, 13.80]]),
+        ]);
+
+        let plans = search
+            .find_routes_to_destination(Point::new(-89.30, 13.70), Point::new(-89.20, 13.80), 2, 0.005)
+            .unwrap();
+        let codes: Vec<_> = plans[0].routes.iter().map(|s| s.route.codigo_de.clone().unwrap()).collect();
+        assert_eq!(codes, vec!["A", "B"]);
+    }
+
+    #[test]
+    fn test_route_variants_are_kept() {
+        let line = || GeoJsonGeometry::LineString {
+            coordinates: vec![vec![-89.20, 13.70], vec![-89.10, 13.70]],
+        };
+        let routes = vec![
+            variant(Some("AB001"), Some("IDA"), line()),
+            variant(Some("AB001"), Some("REGRESO"), line()),
+            variant(Some("AB001"), Some("IDA"), line()),
+            variant(None, None, line()),
+        ];
+
+        let ids = route_variant_ids(&routes);
+        assert!(ids[0].starts_with("AB001/IDA#"));
+        assert_eq!(ids[1], "AB001/REGRESO");
+        assert_eq!(ids[2], format!("{}-2", ids[0]));
+        assert_eq!(ids[3], "SIN_CODIGO/-");
+
+        let (search, _dir) = search(routes);
+        assert_eq!(search.routes.len(), 4);
+
+        // Las variantes de una misma ruta no generan transbordos entre sí
+        let transfers = &search.route_intersections[&ids[0]];
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].to_route, "SIN_CODIGO/-");
+
+        // Y se agrupan en un solo plan por código público
+        let plans = search
+            .find_routes_to_destination(Point::new(-89.20, 13.70), Point::new(-89.10, 13.70), 0, 0.005)
+            .unwrap();
+        assert_eq!(plans.len(), 2);
+    }
+
+    #[test]
+    fn test_variant_ids_do_not_depend_on_load_order() {
+        let line = |lat: f64| GeoJsonGeometry::LineString {
+            coordinates: vec![vec![-89.20, lat], vec![-89.10, lat]],
+        };
+        let routes = vec![
+            variant(Some("AB001"), Some("IDA"), line(13.70)),
+            variant(Some("AB001"), Some("IDA"), line(13.71)),
+        ];
+        let ids = route_variant_ids(&routes);
+        assert_ne!(ids[0], ids[1]);
+
+        let reversed: Vec<_> = routes.iter().rev().cloned().collect();
+        assert_eq!(route_variant_ids(&reversed), vec![ids[1].clone(), ids[0].clone()]);
+    }
+
+    #[test]
+    fn test_intersections_cache_is_tied_to_routes() {
+        let dir = tempfile::tempdir().unwrap();
+        let line = |lng: f64| vec![vec![lng, 13.70], vec![lng, 13.80]];
+        let crossing = route("C", vec![vec![-89.3, 13.75], vec![-89.0, 13.75]]);
+
+        let first = SpatialSearch::new(
+            vec![route("A", line(-89.2)), crossing.clone()],
+            vec![],
+            Some(dir.path().to_path_buf()),
+        );
+        assert!(first.intersections_cache_path().exists());
+
+        // Otras rutas con el mismo directorio: el cache no sirve y se recalcula
+        let second = SpatialSearch::new(vec![route("B", line(-89.1)), crossing], vec![], Some(dir.path().to_path_buf()));
+        assert!(second.route_intersections.contains_key("B/-"));
+        assert!(!second.route_intersections.contains_key("A/-"));
     }
 }
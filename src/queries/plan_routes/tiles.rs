@@ -0,0 +1,476 @@
+//! Teselas vectoriales (Mapbox Vector Tile 2.1) con las rutas y paradas en memoria.
+//!
+//! Se generan a partir de `SpatialSearch`, sin pasar por la base de datos. Las
+//! coordenadas se proyectan a Web Mercator, se recortan al área de la tesela más un
+//! margen y se simplifican con una tolerancia fija en unidades de tesela, de modo que
+//! en grados la simplificación es más agresiva cuanto menor es el zoom.
+
+use geo::Simplify;
+use geo_types::{Coord, LineString, Rect};
+use std::collections::{BTreeSet, HashMap};
+use std::f64::consts::PI;
+
+use super::spatial_search::SpatialSearch;
+
+/// Resolución de la tesela en cada eje.
+pub const EXTENT: u32 = 4096;
+/// Zoom máximo aceptado.
+pub const MAX_ZOOM: u8 = 22;
+/// Por debajo de este zoom no se incluyen paradas.
+pub const STOPS_MIN_ZOOM: u8 = 13;
+/// Margen alrededor de la tesela, en unidades de tesela, para que las líneas no se corten en el borde.
+const BUFFER: f64 = 64.0;
+/// Tolerancia de Douglas-Peucker en unidades de tesela.
+const SIMPLIFY_TOLERANCE: f64 = 1.0;
+
+pub const ROUTES_LAYER: &str = "routes";
+pub const STOPS_LAYER: &str = "stops";
+
+/// Coordenadas `z/x/y` de una tesela en el esquema XYZ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileId {
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TileId {
+    /// `None` si el zoom supera `MAX_ZOOM` o `x`/`y` quedan fuera de la grilla del zoom.
+    pub fn new(z: u8, x: u32, y: u32) -> Option<Self> {
+        let size = 1u64 << z.min(MAX_ZOOM);
+        (z <= MAX_ZOOM && (x as u64) < size && (y as u64) < size).then_some(Self { z, x, y })
+    }
+
+    /// Proyecta lon/lat a unidades de tesela; (0, 0) es la esquina superior izquierda.
+    fn project(&self, lon: f64, lat: f64) -> Coord<f64> {
+        let size = (1u64 << self.z) as f64;
+        let lat = lat.clamp(-85.051_128_78, 85.051_128_78).to_radians();
+        let world_x = (lon + 180.0) / 360.0 * size;
+        let world_y = (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / PI) / 2.0 * size;
+        Coord {
+            x: (world_x - self.x as f64) * EXTENT as f64,
+            y: (world_y - self.y as f64) * EXTENT as f64,
+        }
+    }
+
+    /// Inversa de `project`: de unidades de tesela a lon/lat.
+    fn unproject(&self, point: Coord<f64>) -> Coord<f64> {
+        let size = (1u64 << self.z) as f64;
+        let world_x = self.x as f64 + point.x / EXTENT as f64;
+        let world_y = self.y as f64 + point.y / EXTENT as f64;
+        Coord {
+            x: world_x / size * 360.0 - 180.0,
+            y: (PI * (1.0 - 2.0 * world_y / size)).sinh().atan().to_degrees(),
+        }
+    }
+
+    /// Área en lon/lat que cubre la tesela con su margen `BUFFER`.
+    fn bounds(&self) -> Rect<f64> {
+        let extent = EXTENT as f64;
+        Rect::new(
+            self.unproject(Coord { x: -BUFFER, y: -BUFFER }),
+            self.unproject(Coord { x: extent + BUFFER, y: extent + BUFFER }),
+        )
+    }
+}
+
+/// Recorta el segmento `a`-`b` al cuadrado `[min, max]` (Liang-Barsky).
+fn clip_segment(a: Coord<f64>, b: Coord<f64>, min: f64, max: f64) -> Option<(Coord<f64>, Coord<f64>)> {
+    let delta = b - a;
+    let (mut enter, mut exit) = (0.0f64, 1.0f64);
+    for (p, q) in [
+        (-delta.x, a.x - min),
+        (delta.x, max - a.x),
+        (-delta.y, a.y - min),
+        (delta.y, max - a.y),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                enter = enter.max(t);
+            } else {
+                exit = exit.min(t);
+            }
+        }
+    }
+    // Con `enter == 0` el inicio queda exacto y se puede comparar con el punto anterior
+    let at = |t: f64| if t == 0.0 { a } else if t == 1.0 { b } else { a + delta * t };
+    (enter <= exit).then(|| (at(enter), at(exit)))
+}
+
+/// Tramos de la línea dentro de la tesela, ya simplificados y en enteros.
+fn clip_line(tile: &TileId, coordinates: &[Vec<f64>]) -> Vec<Vec<(i32, i32)>> {
+    let projected: Vec<Coord<f64>> = coordinates
+        .iter()
+        .filter(|coord| coord.len() >= 2)
+        .map(|coord| tile.project(coord[0], coord[1]))
+        .collect();
+
+    let (min, max) = (-BUFFER, EXTENT as f64 + BUFFER);
+    let mut runs: Vec<Vec<Coord<f64>>> = Vec::new();
+    let mut current: Vec<Coord<f64>> = Vec::new();
+    for pair in projected.windows(2) {
+        match clip_segment(pair[0], pair[1], min, max) {
+            Some((start, end)) => {
+                if current.last() != Some(&start) {
+                    runs.push(std::mem::take(&mut current));
+                    current.push(start);
+                }
+                current.push(end);
+            }
+            None => runs.push(std::mem::take(&mut current)),
+        }
+    }
+    runs.push(current);
+
+    runs.into_iter()
+        .filter(|run| run.len() >= 2)
+        .filter_map(|run| {
+            let simplified = LineString::new(run).simplify(&SIMPLIFY_TOLERANCE);
+            let mut points: Vec<(i32, i32)> = Vec::with_capacity(simplified.0.len());
+            for coord in simplified.0 {
+                let point = (coord.x.round() as i32, coord.y.round() as i32);
+                if points.last() != Some(&point) {
+                    points.push(point);
+                }
+            }
+            (points.len() >= 2).then_some(points)
+        })
+        .collect()
+}
+
+/// Tesela con las capas `routes` (code, name, direction, type, subtype, variant_id) y,
+/// desde `STOPS_MIN_ZOOM`, `stops` (name y routes, los códigos separados por coma).
+/// Devuelve un buffer vacío si no hay nada que dibujar.
+pub fn vector_tile<'a>(searches: impl IntoIterator<Item = &'a SpatialSearch>, tile: TileId) -> Vec<u8> {
+    let mut routes = mvt::Layer::new(ROUTES_LAYER);
+    let mut stops = mvt::Layer::new(STOPS_LAYER);
+    // Las paradas compartidas vienen repetidas por ruta; se agrupan por posición
+    let mut stop_routes: HashMap<(i32, i32), (Option<String>, BTreeSet<String>)> = HashMap::new();
+
+    let bounds = tile.bounds();
+
+    for search in searches {
+        // Solo se recortan las rutas cuya caja toca la tesela
+        let mut variants: Vec<_> = search.routes_within(bounds).collect();
+        variants.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (variant_id, route) in variants {
+            let lines: Vec<_> = route
+                .geometry
+                .line_parts()
+                .into_iter()
+                .flat_map(|part| clip_line(&tile, part))
+                .collect();
+            if lines.is_empty() {
+                continue;
+            }
+
+            let properties = &route.properties;
+            routes.add_feature(
+                mvt::Geometry::LineString(lines),
+                &[
+                    ("variant_id", Some(variant_id)),
+                    ("code", properties.codigo_de.as_deref()),
+                    ("name", properties.nombre_de.as_deref()),
+                    ("direction", properties.sentido.as_deref()),
+                    ("type", properties.tipo.as_deref()),
+                    ("subtype", properties.subtipo.as_deref()),
+                ],
+            );
+        }
+
+        if tile.z < STOPS_MIN_ZOOM {
+            continue;
+        }
+        for stop in search.bus_stops() {
+            let (Some(lat), Some(lon)) = (stop.latitud, stop.longitud) else {
+                continue;
+            };
+            let point = tile.project(lon, lat);
+            if !(0.0..EXTENT as f64).contains(&point.x) || !(0.0..EXTENT as f64).contains(&point.y) {
+                continue;
+            }
+
+            let entry = stop_routes
+                .entry((point.x as i32, point.y as i32))
+                .or_insert_with(|| (None, BTreeSet::new()));
+            if entry.0.is_none() {
+                entry.0 = stop.nam.clone();
+            }
+            entry.1.extend(stop.ruta.clone());
+        }
+    }
+
+    let mut grouped: Vec<_> = stop_routes.into_iter().collect();
+    grouped.sort_by_key(|(position, _)| *position);
+    for (position, (name, codes)) in grouped {
+        let codes = codes.into_iter().collect::<Vec<_>>().join(",");
+        stops.add_feature(
+            mvt::Geometry::Point(position),
+            &[("name", name.as_deref()), ("routes", Some(codes.as_str()))],
+        );
+    }
+
+    mvt::encode([routes, stops])
+}
+
+/// Codificación protobuf mínima del formato MVT: solo lo necesario para escribir
+/// capas con puntos y líneas cuyas propiedades son cadenas.
+mod mvt {
+    use super::EXTENT;
+    use std::collections::HashMap;
+
+    const MOVE_TO: u32 = 1;
+    const LINE_TO: u32 = 2;
+    const POINT: u64 = 1;
+    const LINESTRING: u64 = 2;
+
+    pub enum Geometry {
+        Point((i32, i32)),
+        LineString(Vec<Vec<(i32, i32)>>),
+    }
+
+    pub struct Layer {
+        name: &'static str,
+        features: Vec<Vec<u8>>,
+        keys: Vec<String>,
+        values: Vec<String>,
+        key_index: HashMap<String, u32>,
+        value_index: HashMap<String, u32>,
+    }
+
+    fn varint(buf: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        buf.push(value as u8);
+    }
+
+    fn tag(buf: &mut Vec<u8>, field: u32, wire_type: u32) {
+        varint(buf, ((field << 3) | wire_type) as u64);
+    }
+
+    fn varint_field(buf: &mut Vec<u8>, field: u32, value: u64) {
+        tag(buf, field, 0);
+        varint(buf, value);
+    }
+
+    fn bytes_field(buf: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+        tag(buf, field, 2);
+        varint(buf, bytes.len() as u64);
+        buf.extend_from_slice(bytes);
+    }
+
+    fn packed_field(buf: &mut Vec<u8>, field: u32, values: &[u32]) {
+        let mut packed = Vec::with_capacity(values.len());
+        for value in values {
+            varint(&mut packed, *value as u64);
+        }
+        bytes_field(buf, field, &packed);
+    }
+
+    fn zigzag(value: i32) -> u32 {
+        ((value << 1) ^ (value >> 31)) as u32
+    }
+
+    fn command(id: u32, count: usize) -> u32 {
+        (id & 0x7) | ((count as u32) << 3)
+    }
+
+    /// Comandos de geometría con coordenadas relativas al cursor.
+    fn geometry_commands(geometry: &Geometry) -> Vec<u32> {
+        let mut commands = Vec::new();
+        let mut cursor = (0i32, 0i32);
+        let mut move_to = |commands: &mut Vec<u32>, point: (i32, i32)| {
+            commands.push(zigzag(point.0 - cursor.0));
+            commands.push(zigzag(point.1 - cursor.1));
+            cursor = point;
+        };
+
+        match geometry {
+            Geometry::Point(point) => {
+                commands.push(command(MOVE_TO, 1));
+                move_to(&mut commands, *point);
+            }
+            Geometry::LineString(lines) => {
+                for line in lines {
+                    commands.push(command(MOVE_TO, 1));
+                    move_to(&mut commands, line[0]);
+                    commands.push(command(LINE_TO, line.len() - 1));
+                    for point in &line[1..] {
+                        move_to(&mut commands, *point);
+                    }
+                }
+            }
+        }
+        commands
+    }
+
+    fn intern(index: &mut HashMap<String, u32>, list: &mut Vec<String>, value: &str) -> u32 {
+        if let Some(position) = index.get(value) {
+            return *position;
+        }
+        let position = list.len() as u32;
+        list.push(value.to_string());
+        index.insert(value.to_string(), position);
+        position
+    }
+
+    impl Layer {
+        pub fn new(name: &'static str) -> Self {
+            Self {
+                name,
+                features: Vec::new(),
+                keys: Vec::new(),
+                values: Vec::new(),
+                key_index: HashMap::new(),
+                value_index: HashMap::new(),
+            }
+        }
+
+        pub fn len(&self) -> usize {
+            self.features.len()
+        }
+
+        /// Agrega una feature; las propiedades en `None` se omiten.
+        pub fn add_feature(&mut self, geometry: Geometry, properties: &[(&str, Option<&str>)]) {
+            let mut tags = Vec::new();
+            for (key, value) in properties {
+                if let Some(value) = value {
+                    tags.push(intern(&mut self.key_index, &mut self.keys, key));
+                    tags.push(intern(&mut self.value_index, &mut self.values, value));
+                }
+            }
+
+            let mut feature = Vec::new();
+            varint_field(&mut feature, 1, self.features.len() as u64 + 1);
+            packed_field(&mut feature, 2, &tags);
+            let kind = match geometry {
+                Geometry::Point(_) => POINT,
+                Geometry::LineString(_) => LINESTRING,
+            };
+            varint_field(&mut feature, 3, kind);
+            packed_field(&mut feature, 4, &geometry_commands(&geometry));
+            self.features.push(feature);
+        }
+
+        fn encode(&self) -> Vec<u8> {
+            let mut layer = Vec::new();
+            varint_field(&mut layer, 15, 2);
+            bytes_field(&mut layer, 1, self.name.as_bytes());
+            for feature in &self.features {
+                bytes_field(&mut layer, 2, feature);
+            }
+            for key in &self.keys {
+                bytes_field(&mut layer, 3, key.as_bytes());
+            }
+            for value in &self.values {
+                // Value { string_value = 1 }
+                let mut encoded = Vec::new();
+                bytes_field(&mut encoded, 1, value.as_bytes());
+                bytes_field(&mut layer, 4, &encoded);
+            }
+            varint_field(&mut layer, 5, EXTENT as u64);
+            layer
+        }
+    }
+
+    /// Tile { repeated Layer layers = 3 }, sin las capas vacías.
+    pub fn encode(layers: impl IntoIterator<Item = Layer>) -> Vec<u8> {
+        let mut tile = Vec::new();
+        for layer in layers {
+            if layer.len() > 0 {
+                bytes_field(&mut tile, 3, &layer.encode());
+            }
+        }
+        tile
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_geometry_commands() {
+            assert_eq!(zigzag(0), 0);
+            assert_eq!(zigzag(-1), 1);
+            assert_eq!(zigzag(1), 2);
+
+            // Ejemplo de la especificación: línea (2,2) → (2,10) → (10,10)
+            let line = Geometry::LineString(vec![vec![(2, 2), (2, 10), (10, 10)]]);
+            assert_eq!(geometry_commands(&line), vec![9, 4, 4, 18, 0, 16, 16, 0]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan_routes::fixtures::{bus_stop, route};
+
+    fn contains(haystack: &[u8], needle: &str) -> bool {
+        haystack.windows(needle.len()).any(|window| window == needle.as_bytes())
+    }
+
+    #[test]
+    fn test_tile_id_bounds() {
+        assert!(TileId::new(0, 0, 0).is_some());
+        assert!(TileId::new(2, 3, 3).is_some());
+        assert!(TileId::new(2, 4, 0).is_none());
+        assert!(TileId::new(MAX_ZOOM + 1, 0, 0).is_none());
+
+        let tile = TileId::new(1, 0, 0).unwrap();
+        let corner = tile.project(-180.0, 85.051_128_78);
+        assert!(corner.x.abs() < 1e-6 && corner.y.abs() < 1e-3);
+
+        let tile = TileId::new(14, 4132, 7562).unwrap();
+        let point = tile.project(-89.19, 13.69);
+        let back = tile.unproject(point);
+        assert!((back.x + 89.19).abs() < 1e-9 && (back.y - 13.69).abs() < 1e-9);
+        let bounds = tile.bounds();
+        assert!(bounds.min().x < -89.19 && -89.19 < bounds.max().x);
+        assert!(bounds.min().y < 13.69 && 13.69 < bounds.max().y);
+    }
+
+    #[test]
+    fn test_clip_line_to_tile() {
+        // Una línea horizontal que atraviesa la tesela 0/0/0 de lado a lado más allá del margen
+        let tile = TileId::new(0, 0, 0).unwrap();
+        let lines = clip_line(&tile, &[vec![-200.0, 0.0], vec![0.0, 0.0], vec![200.0, 0.0]]);
+        assert_eq!(lines, vec![vec![(-64, 2048), (4160, 2048)]]);
+    }
+
+    #[test]
+    fn test_vector_tile_layers() {
+        let mut line = route("A", vec![vec![-89.20, 13.70], vec![-89.19, 13.70], vec![-89.18, 13.70]]);
+        line.properties.tipo = Some("URBANO".into());
+        line.properties.subtipo = Some("AUTOBUS".into());
+        let mut stop = bus_stop("A", -89.19, 13.70);
+        stop.nam = Some("Parada Centro".into());
+        let search = SpatialSearch::without_cache(vec![line], vec![stop, bus_stop("B", -89.19, 13.70)]);
+
+        // Tesela z14 que contiene la ruta
+        let (z, lon, lat) = (14u8, -89.19f64, 13.70f64);
+        let size = (1u32 << z) as f64;
+        let x = ((lon + 180.0) / 360.0 * size) as u32;
+        let lat_rad = lat.to_radians();
+        let y = ((1.0 - (lat_rad.tan() + 1.0 / lat_rad.cos()).ln() / PI) / 2.0 * size) as u32;
+
+        let tile = vector_tile([&search], TileId::new(z, x, y).unwrap());
+        assert!(contains(&tile, ROUTES_LAYER) && contains(&tile, STOPS_LAYER));
+        assert!(contains(&tile, "subtype") && contains(&tile, "AUTOBUS"));
+        // La parada compartida se escribe una vez con las dos rutas
+        assert!(contains(&tile, "A,B") && contains(&tile, "Parada Centro"));
+
+        // A zoom bajo no hay paradas, y lejos de la ruta la tesela queda vacía
+        let low = vector_tile([&search], TileId::new(8, x >> 6, y >> 6).unwrap());
+        assert!(contains(&low, ROUTES_LAYER) && !contains(&low, STOPS_LAYER));
+        assert!(vector_tile([&search], TileId::new(z, 0, 0).unwrap()).is_empty());
+    }
+}
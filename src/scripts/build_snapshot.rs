@@ -0,0 +1,100 @@
+use geo_reader::{PlannerSnapshot, RegionConfig, RegionsConfig, SpatialSearch};
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Instant;
+use tracing::{error, info};
+
+const USAGE: &str = "Uso: build_snapshot [--region ID] [--output ARCHIVO]";
+
+struct Args {
+    region: Option<String>,
+    output: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        region: None,
+        output: None,
+    };
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| format!("Falta el valor de {}", arg));
+        match arg.as_str() {
+            "--region" | "-r" => args.region = Some(value()?),
+            "--output" | "-o" => args.output = Some(PathBuf::from(value()?)),
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            other => return Err(format!("Opción desconocida: {}\n{}", other, USAGE)),
+        }
+    }
+
+    Ok(args)
+}
+
+async fn build(region: &RegionConfig, output: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    info!("Construyendo snapshot de la región {}", region.id);
+
+    let source = region.load_source().await?;
+    let search = SpatialSearch::without_cache(
+        source.routes().features.clone(),
+        source.bus_stops().features.iter()
+            .map(|feature| feature.properties.clone())
+            .collect(),
+    );
+
+    PlannerSnapshot::build(&region.id, region.dataset_version(), source.as_ref(), &search)?.write(&output)?;
+    info!(
+        "Snapshot de {} escrito en {} ({} ms)",
+        region.id,
+        output.display(),
+        started.elapsed().as_millis()
+    );
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    let config = match RegionsConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Error al cargar las regiones: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let regions: Vec<&RegionConfig> = config
+        .regions
+        .iter()
+        .filter(|region| args.region.as_deref().is_none_or(|id| region.id == id))
+        .collect();
+    if regions.is_empty() {
+        error!("No hay regiones que coincidan");
+        return ExitCode::from(2);
+    }
+    if args.output.is_some() && regions.len() > 1 {
+        eprintln!("--output requiere --region cuando hay varias regiones");
+        return ExitCode::from(2);
+    }
+
+    for region in regions {
+        let output = args.output.clone().unwrap_or_else(|| region.snapshot_path());
+        if let Err(e) = build(region, output).await {
+            error!("Error al construir el snapshot de {}: {}", region.id, e);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    ExitCode::SUCCESS
+}
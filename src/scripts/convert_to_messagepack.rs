@@ -1,9 +1,11 @@
+use geo_reader::plan_routes::manifest::{DataManifest, LayerConfig, LayerKind};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::env;
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tracing::{error, info, instrument, warn};
 
 // Estructuras de datos principales
@@ -45,18 +47,8 @@ struct Feature<T> {
     geometry: Geometry,
 }
 
-// Configuración
-#[derive(Debug, Deserialize)]
-struct FileConfig {
-    input_path: String,
-    type_name: String,
-    output_path: String,
-}
-
-#[derive(Debug, Deserialize)]
-struct Config {
-    files: Vec<FileConfig>,
-}
+/// Manifiesto usado si no se pasa uno como argumento ni en `DATA_MANIFEST`.
+const DEFAULT_MANIFEST: &str = "./data/manifest.toml";
 
 // Manejo de errores personalizado
 #[derive(Debug, thiserror::Error)]
@@ -67,6 +59,8 @@ enum ConversionError {
     Json(#[from] serde_json::Error),
     #[error("Error de MessagePack: {0}")]
     MessagePack(#[from] rmp_serde::encode::Error),
+    #[error("Error en el manifiesto: {0}")]
+    Manifest(#[from] geo_reader::plan_routes::data_loader::LoaderError),
     #[error("No hay features en el archivo")]
     NoFeatures,
     #[error("Error de validación: {0}")]
@@ -93,7 +87,7 @@ impl Validate for LimDepartamentales {
 impl Validate for ParadaTransporte {
     fn validate(&self) -> Result<(), ConversionError> {
         if let (Some(lat), Some(lon)) = (self.latitud, self.longitud) {
-            if lat < -90.0 || lat > 90.0 || lon < -180.0 || lon > 180.0 {
+            if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
                 return Err(ConversionError::Validation(
                     "Coordenadas geográficas inválidas".into(),
                 ));
@@ -116,15 +110,15 @@ impl Validate for Ruta {
     }
 }
 
-#[instrument(skip(input_path, output_path))]
-fn convert_geojson_to_bin<T>(input_path: &str, output_path: &str) -> Result<(), ConversionError>
+#[instrument(skip(input_path, output_path, layer))]
+fn convert_geojson_to_bin<T>(input_path: &Path, output_path: &Path, layer: &LayerConfig) -> Result<(), ConversionError>
 where
     T: for<'de> Deserialize<'de> + Serialize + std::fmt::Debug + Validate,
 {
-    info!("Iniciando conversión de {}", input_path);
+    info!("Iniciando conversión de {}", input_path.display());
 
     // Crear directorio de salida si no existe
-    if let Some(parent) = Path::new(output_path).parent() {
+    if let Some(parent) = output_path.parent() {
         std::fs::create_dir_all(parent)?;
     }
 
@@ -151,7 +145,11 @@ where
             coordinates: feature["geometry"]["coordinates"].clone(),
         };
 
-        let properties: T = serde_json::from_value(feature["properties"].clone())?;
+        let mut raw_properties = feature["properties"].clone();
+        if let Value::Object(map) = &mut raw_properties {
+            layer.map_properties(map);
+        }
+        let properties: T = serde_json::from_value(raw_properties)?;
         
         // Validar los datos antes de escribir
         properties.validate()?;
@@ -168,24 +166,21 @@ where
         }
     }
 
-    info!("Conversión completada exitosamente: {}", output_path);
+    info!("Conversión completada exitosamente: {}", output_path.display());
     Ok(())
 }
 
-fn process_file(config: &FileConfig) -> Result<(), ConversionError> {
-    info!("Procesando archivo: {}", config.input_path);
-    
-    match config.type_name.as_str() {
-        "LimDepartamentales" => convert_geojson_to_bin::<LimDepartamentales>(&config.input_path, &config.output_path),
-        "ParadaTransporte" => convert_geojson_to_bin::<ParadaTransporte>(&config.input_path, &config.output_path),
-        "Ruta" => convert_geojson_to_bin::<Ruta>(&config.input_path, &config.output_path),
-        _ => {
-            error!("Tipo desconocido: {}", config.type_name);
-            Err(ConversionError::Validation(format!(
-                "Tipo desconocido: {}",
-                config.type_name
-            )))
+fn process_layer(base_dir: &Path, layer: &LayerConfig) -> Result<(), ConversionError> {
+    let input_path = base_dir.join(&layer.path);
+    let output_path = layer.binary_path(base_dir);
+    info!("Procesando archivo: {}", input_path.display());
+
+    match layer.kind {
+        LayerKind::Departments | LayerKind::Municipalities => {
+            convert_geojson_to_bin::<LimDepartamentales>(&input_path, &output_path, layer)
         }
+        LayerKind::Stops => convert_geojson_to_bin::<ParadaTransporte>(&input_path, &output_path, layer),
+        LayerKind::Routes => convert_geojson_to_bin::<Ruta>(&input_path, &output_path, layer),
     }
 }
 
@@ -193,37 +188,55 @@ fn main() {
     // Inicializar el sistema de logging
     tracing_subscriber::fmt::init();
 
-    // Cargar configuración
-    let config: Config = match toml::from_str(include_str!("config.toml")) {
-        Ok(config) => config,
+    // Cargar el manifiesto de datos
+    let manifest_path = env::args()
+        .nth(1)
+        .or_else(|| env::var("DATA_MANIFEST").ok())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_MANIFEST));
+    let manifest = match DataManifest::load(&manifest_path) {
+        Ok(manifest) => manifest,
         Err(e) => {
-            error!("Error al cargar la configuración: {}", e);
+            error!("Error al cargar el manifiesto {}: {}", manifest_path.display(), e);
             return;
         }
     };
+    let base_dir = manifest_path.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+    // Las capas opcionales que no existen se omiten
+    let layers: Vec<&LayerConfig> = manifest
+        .layers
+        .iter()
+        .filter(|layer| {
+            let exists = base_dir.join(&layer.path).exists();
+            if !exists && layer.optional {
+                warn!("Capa opcional no encontrada: {}", layer.path.display());
+            }
+            exists || !layer.optional
+        })
+        .collect();
 
     // Procesar archivos en paralelo
-    let results: Vec<Result<(), ConversionError>> = config
-        .files
+    let results: Vec<Result<(), ConversionError>> = layers
         .par_iter()
-        .map(|file_config| process_file(file_config))
+        .map(|layer| process_layer(&base_dir, layer))
         .collect();
 
     // Reportar resultados
     let mut success_count = 0;
     let mut error_count = 0;
 
-    for (index, result) in results.iter().enumerate() {
+    for (layer, result) in layers.iter().zip(&results) {
         match result {
             Ok(_) => {
                 success_count += 1;
-                info!("Archivo {} procesado exitosamente", config.files[index].input_path);
+                info!("Archivo {} procesado exitosamente", layer.path.display());
             }
             Err(e) => {
                 error_count += 1;
                 error!(
                     "Error al procesar {}: {}",
-                    config.files[index].input_path,
+                    layer.path.display(),
                     e
                 );
             }
@@ -285,16 +298,15 @@ mod tests {
         }"#;
 
         let temp_file = NamedTempFile::new()?;
-        let temp_path = temp_file.path().to_str().unwrap();
+        let input_file = NamedTempFile::new()?;
 
         // Escribir JSON temporal
-        std::fs::write("temp.json", input_json)?;
+        std::fs::write(input_file.path(), input_json)?;
 
         // Probar conversión
-        convert_geojson_to_bin::<LimDepartamentales>("temp.json", temp_path)?;
-
-        // Limpiar
-        std::fs::remove_file("temp.json")?;
+        let manifest = DataManifest::default();
+        let layer = manifest.layers_of(LayerKind::Departments).next().unwrap();
+        convert_geojson_to_bin::<LimDepartamentales>(input_file.path(), temp_file.path(), layer)?;
         Ok(())
     }
 }
\ No newline at end of file
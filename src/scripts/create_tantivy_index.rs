@@ -1,9 +1,11 @@
+use geo_reader::plan_routes::manifest::{DataManifest, LayerKind};
 use serde::{Deserialize, Serialize};
+use std::env;
 use std::fs::{File, create_dir_all, remove_dir_all};
 use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tantivy::{schema::*, Document, Index};
-use tracing::{info, error, instrument};
+use tracing::{info, instrument, warn};
 use serde_json::Value;
 use rmp_serde::Deserializer;
 
@@ -54,9 +56,14 @@ enum IndexError {
     #[error("Error de MessagePack: {0}")]
     MessagePack(String),
     #[error("Error en la geometría: {0}")]
+    #[allow(dead_code)]
     Geometry(String),
+    #[error("Error en el manifiesto: {0}")]
+    Manifest(#[from] geo_reader::plan_routes::data_loader::LoaderError),
 }
 
+// Algunos campos solo se registran en el esquema; aún no se llenan al indexar
+#[allow(dead_code)]
 struct IndexFields {
     name: Field,
     tipo: Field,
@@ -281,49 +288,50 @@ fn main() -> Result<(), IndexError> {
     let index = create_or_open_index(&index_path, schema)?;
     let mut index_writer = index.writer(200_000_000)?;
     
-    let files = vec![
-        ("../../data/LIM DEPARTAMENTALES.bin", "LimDepartamentales"),
-        ("../../data/Paradas Transporte Colectivo AMSS.bin", "ParadaTransporte"),
-        ("../../data/Rutas Interdepartamentales.bin", "Ruta"),
-        ("../../data/Rutas Interurbanas.bin", "Ruta"),
-        ("../../data/Rutas Urbanas.bin", "Ruta"),
-    ];
+    let manifest_path = env::args()
+        .nth(1)
+        .or_else(|| env::var("DATA_MANIFEST").ok())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("data").join("manifest.toml"));
+    let manifest = DataManifest::load(&manifest_path)?;
+    let base_dir = manifest_path.parent().unwrap_or(Path::new(".")).to_path_buf();
 
-    for (file_path, tipo) in files {
-        info!("Procesando archivo: {}", file_path);
+    for layer in &manifest.layers {
+        let file_path = layer.binary_path(&base_dir);
+        if layer.optional && !file_path.exists() {
+            warn!("Capa opcional no encontrada: {}", file_path.display());
+            continue;
+        }
+        info!("Procesando archivo: {}", file_path.display());
         
-        let file = File::open(file_path)?;
+        let file = File::open(&file_path)?;
         let reader = BufReader::new(file);
         
-        match tipo {
-            "LimDepartamentales" => {
+        match layer.kind {
+            LayerKind::Departments | LayerKind::Municipalities => {
                 let features = read_features::<LimDepartamentales>(reader)?;
                 info!("Leídos {} features", features.len());
                 for feature in features {
                     index_feature(feature, &mut index_writer, &fields)?;
                 }
             },
-            "ParadaTransporte" => {
+            LayerKind::Stops => {
                 let features = read_features::<ParadaTransporte>(reader)?;
                 info!("Leídos {} features", features.len());
                 for feature in features {
                     index_feature(feature, &mut index_writer, &fields)?;
                 }
             },
-            "Ruta" => {
+            LayerKind::Routes => {
                 let features = read_features::<Ruta>(reader)?;
                 info!("Leídos {} features", features.len());
                 for feature in features {
                     index_feature(feature, &mut index_writer, &fields)?;
                 }
             },
-            _ => {
-                error!("Tipo desconocido: {}", tipo);
-                continue;
-            }
         }
         
-        info!("Completado: {}", file_path);
+        info!("Completado: {}", file_path.display());
     }
     
     index_writer.commit()?;
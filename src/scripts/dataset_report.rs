@@ -0,0 +1,103 @@
+use geo_reader::{DataLoader, QualityConfig, QualityReport};
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+use tracing::{error, info};
+
+const USAGE: &str = "Uso: dataset_report [DATA_DIR] [--format json|markdown] [--output ARCHIVO] [--max-stop-distance METROS] [--length-tolerance FRACCION]";
+
+struct Args {
+    data_dir: String,
+    markdown: bool,
+    output: Option<String>,
+    config: QualityConfig,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        data_dir: "./data".to_string(),
+        markdown: false,
+        output: None,
+        config: QualityConfig::default(),
+    };
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| format!("Falta el valor de {}", arg));
+        match arg.as_str() {
+            "--format" => match value()?.as_str() {
+                "json" => args.markdown = false,
+                "markdown" | "md" => args.markdown = true,
+                other => return Err(format!("Formato desconocido: {}", other)),
+            },
+            "--output" | "-o" => args.output = Some(value()?),
+            "--max-stop-distance" => {
+                args.config.max_stop_distance_m = value()?.parse().map_err(|e| format!("{}", e))?
+            }
+            "--length-tolerance" => {
+                args.config.length_tolerance = value()?.parse().map_err(|e| format!("{}", e))?
+            }
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            other if !other.starts_with('-') => args.data_dir = other.to_string(),
+            other => return Err(format!("Opción desconocida: {}\n{}", other, USAGE)),
+        }
+    }
+
+    Ok(args)
+}
+
+fn main() -> ExitCode {
+    // Los logs van a stderr para no mezclarse con el reporte en stdout
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut loader = DataLoader::new(&args.data_dir);
+    if let Err(e) = loader.load_all() {
+        error!("Error al cargar {}: {}", args.data_dir, e);
+        return ExitCode::from(2);
+    }
+
+    let report = QualityReport::from_source(&loader, &args.config);
+    let rendered = if args.markdown {
+        report.to_markdown()
+    } else {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => json,
+            Err(e) => {
+                error!("Error al serializar el reporte: {}", e);
+                return ExitCode::from(2);
+            }
+        }
+    };
+
+    match &args.output {
+        Some(path) => {
+            if let Err(e) = fs::write(path, rendered) {
+                error!("Error al escribir {}: {}", path, e);
+                return ExitCode::from(2);
+            }
+            info!("Reporte escrito en {}", path);
+        }
+        None => println!("{}", rendered),
+    }
+
+    info!(
+        "Errores: {}, advertencias: {}",
+        report.error_count(),
+        report.warning_count()
+    );
+
+    // Código de salida distinto de cero si hay errores, para usarlo antes de publicar
+    if report.error_count() > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,282 @@
+use geo_reader::{
+    feature_collection, plan_features, PlanExplanation, PlannerRegistry, RegionsConfig, RoutePlan,
+};
+use geo_types::Point;
+use serde::Serialize;
+use serde_json::Value;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::process::ExitCode;
+use tracing::{error, info};
+
+const USAGE: &str = "Uso: geo-plan [ORIGEN_LAT,LNG DESTINO_LAT,LNG | --csv ARCHIVO] [--region ID] [--format table|json|geojson] [--output ARCHIVO] [--explain]
+
+El CSV lleva encabezado con origin_lat, origin_lng, destination_lat, destination_lng y opcionalmente id.";
+
+#[derive(PartialEq)]
+enum Format {
+    Table,
+    Json,
+    GeoJson,
+}
+
+struct Args {
+    pairs: Vec<OdPair>,
+    region: Option<String>,
+    format: Format,
+    output: Option<String>,
+    explain: bool,
+}
+
+struct OdPair {
+    id: String,
+    origin: Point<f64>,
+    destination: Point<f64>,
+}
+
+#[derive(Serialize)]
+struct PlanResult {
+    id: String,
+    region: Option<String>,
+    origin: (f64, f64),      // (lat, lng)
+    destination: (f64, f64), // (lat, lng)
+    plans: Vec<RoutePlan>,
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    explain: Option<PlanExplanation>,
+    #[serde(skip)]
+    features: Vec<Value>,
+}
+
+fn parse_point(value: &str) -> Result<Point<f64>, String> {
+    let (lat, lng) = value
+        .split_once(',')
+        .ok_or_else(|| format!("Coordenada inválida (se espera LAT,LNG): {}", value))?;
+    let lat: f64 = lat.trim().parse().map_err(|e| format!("Latitud inválida {}: {}", lat, e))?;
+    let lng: f64 = lng.trim().parse().map_err(|e| format!("Longitud inválida {}: {}", lng, e))?;
+    Ok(Point::new(lng, lat))
+}
+
+fn read_csv(path: &str) -> Result<Vec<OdPair>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Error al leer {}: {}", path, e))?;
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<&str> = lines
+        .next()
+        .ok_or_else(|| format!("{} está vacío", path))?
+        .split(',')
+        .map(str::trim)
+        .collect();
+    let column = |name: &str| header.iter().position(|h| *h == name);
+    let required = |name: &str| column(name).ok_or_else(|| format!("Falta la columna {} en {}", name, path));
+    let (origin_lat, origin_lng) = (required("origin_lat")?, required("origin_lng")?);
+    let (dest_lat, dest_lng) = (required("destination_lat")?, required("destination_lng")?);
+    let id_column = column("id");
+
+    lines
+        .enumerate()
+        .map(|(index, line)| {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let field = |i: usize| {
+                fields
+                    .get(i)
+                    .ok_or_else(|| format!("Línea {}: faltan columnas", index + 2))
+                    .and_then(|value| value.parse::<f64>().map_err(|e| format!("Línea {}: {}", index + 2, e)))
+            };
+            Ok(OdPair {
+                id: id_column
+                    .and_then(|i| fields.get(i))
+                    .map(|id| id.to_string())
+                    .unwrap_or_else(|| (index + 1).to_string()),
+                origin: Point::new(field(origin_lng)?, field(origin_lat)?),
+                destination: Point::new(field(dest_lng)?, field(dest_lat)?),
+            })
+        })
+        .collect()
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        pairs: Vec::new(),
+        region: None,
+        format: Format::Table,
+        output: None,
+        explain: false,
+    };
+    let mut points = Vec::new();
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| format!("Falta el valor de {}", arg));
+        match arg.as_str() {
+            "--csv" => args.pairs.extend(read_csv(&value()?)?),
+            "--region" | "-r" => args.region = Some(value()?),
+            "--format" | "-f" => {
+                args.format = match value()?.as_str() {
+                    "table" => Format::Table,
+                    "json" => Format::Json,
+                    "geojson" => Format::GeoJson,
+                    other => return Err(format!("Formato desconocido: {}", other)),
+                }
+            }
+            "--output" | "-o" => args.output = Some(value()?),
+            "--explain" => args.explain = true,
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            // Las coordenadas negativas empiezan con '-', así que se aceptan si parsean
+            other if parse_point(other).is_ok() => points.push(parse_point(other)?),
+            other => return Err(format!("Opción desconocida: {}\n{}", other, USAGE)),
+        }
+    }
+
+    match points.as_slice() {
+        [] => {}
+        [origin, destination] => args.pairs.push(OdPair {
+            id: "1".to_string(),
+            origin: *origin,
+            destination: *destination,
+        }),
+        _ => return Err(format!("Se esperan un origen y un destino\n{}", USAGE)),
+    }
+    if args.pairs.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    Ok(args)
+}
+
+fn plan(registry: &PlannerRegistry, args: &Args, pair: &OdPair) -> PlanResult {
+    let mut result = PlanResult {
+        id: pair.id.clone(),
+        region: None,
+        origin: (pair.origin.y(), pair.origin.x()),
+        destination: (pair.destination.y(), pair.destination.x()),
+        plans: Vec::new(),
+        error: None,
+        explain: None,
+        features: Vec::new(),
+    };
+
+    let region = match registry.resolve(args.region.as_deref(), pair.origin) {
+        Ok(region) => region,
+        Err(e) => {
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+    result.region = Some(region.config.id.clone());
+
+    match region.planner.plan_route(pair.origin, pair.destination) {
+        Ok(plans) => result.plans = plans,
+        Err(e) => result.error = Some(e.to_string()),
+    }
+    if args.explain {
+        match region.planner.explain(pair.origin, pair.destination) {
+            Ok(explanation) => result.explain = Some(explanation),
+            Err(e) => error!("No se pudo explicar {}: {}", pair.id, e),
+        }
+    }
+    if args.format == Format::GeoJson {
+        result.features = plan_features(region.planner.search(), pair.origin, pair.destination, &result.plans);
+        for feature in &mut result.features {
+            feature["properties"]["od"] = Value::from(pair.id.clone());
+        }
+    }
+
+    result
+}
+
+fn render_table(results: &[PlanResult]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{:<8} {:<8} {:>4}  {:<50} {:>10} {:>10}", "id", "region", "plan", "rutas", "transb.", "distancia");
+    for result in results {
+        let region = result.region.as_deref().unwrap_or("-");
+        if let Some(e) = &result.error {
+            let _ = writeln!(out, "{:<8} {:<8} {:>4}  error: {}", result.id, region, "-", e);
+        }
+        for (rank, plan) in result.plans.iter().enumerate() {
+            let routes: Vec<&str> = plan.routes.iter().map(|segment| segment.variant_id.as_str()).collect();
+            let _ = writeln!(
+                out,
+                "{:<8} {:<8} {:>4}  {:<50} {:>10} {:>10.4}",
+                result.id,
+                region,
+                rank + 1,
+                routes.join(" > "),
+                plan.transfers_count,
+                plan.total_distance
+            );
+        }
+
+        if let Some(explain) = &result.explain {
+            let _ = writeln!(out, "  departamentos: {:?} -> {:?} (interdepartamental: {})",
+                explain.origin_department, explain.destination_department, explain.is_interdepartmental);
+            let _ = writeln!(out, "  radio: {}  transbordos máx.: {}", explain.max_route_distance, explain.max_transfers);
+            let _ = writeln!(out, "  candidatas en origen ({}): {}", explain.origin_candidates.len(), explain.origin_candidates.join(", "));
+            let _ = writeln!(out, "  candidatas en destino ({}): {}", explain.destination_candidates.len(), explain.destination_candidates.join(", "));
+            for plan in &explain.plans {
+                let _ = writeln!(out, "  puntaje {:>8.3}  {}", plan.score, plan.variant_ids.join(" > "));
+            }
+            if let Some(e) = &explain.error {
+                let _ = writeln!(out, "  búsqueda: {}", e);
+            }
+        }
+    }
+    out
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    // Los logs van a stderr para no mezclarse con la salida
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    // Mismos datos que `initialize_planner`
+    let registry = match RegionsConfig::from_env() {
+        Ok(config) => PlannerRegistry::build(&config).await,
+        Err(e) => Err(e),
+    };
+    let registry = match registry {
+        Ok(registry) => registry,
+        Err(e) => {
+            error!("Error al cargar el planificador: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let results: Vec<PlanResult> = args.pairs.iter().map(|pair| plan(&registry, &args, pair)).collect();
+    let failed = results.iter().filter(|result| result.plans.is_empty()).count();
+
+    let rendered = match args.format {
+        Format::Table => render_table(&results),
+        Format::Json => serde_json::to_string_pretty(&results).unwrap_or_default(),
+        Format::GeoJson => {
+            let features = results.into_iter().flat_map(|result| result.features).collect();
+            serde_json::to_string_pretty(&feature_collection(features)).unwrap_or_default()
+        }
+    };
+
+    match &args.output {
+        Some(path) => {
+            if let Err(e) = fs::write(path, rendered) {
+                error!("Error al escribir {}: {}", path, e);
+                return ExitCode::from(2);
+            }
+            info!("Resultado escrito en {}", path);
+        }
+        None => print!("{}", rendered),
+    }
+
+    if failed > 0 {
+        info!("{} de {} consultas sin planes", failed, args.pairs.len());
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -1,14 +1,13 @@
-use crate::db::connect_to_db;
+use crate::db::{connect_to_db, DbError};
 use ordered_float::OrderedFloat;
 use std::collections::BTreeMap;
-use tokio_postgres::Error;
 
 /// Alias para facilitar el manejo de coordenadas.
 type Coordinate = (OrderedFloat<f64>, OrderedFloat<f64>);
 type Graph = BTreeMap<Coordinate, BTreeMap<Coordinate, OrderedFloat<f64>>>;
 
 /// Carga el grafo desde la base de datos PostgreSQL.
-pub async fn load_graph_from_db() -> Result<Graph, Error> {
+pub async fn load_graph_from_db() -> Result<Graph, DbError> {
     let client = connect_to_db().await?;
 
     let query = "
@@ -21,7 +20,7 @@ pub async fn load_graph_from_db() -> Result<Graph, Error> {
         FROM planet_osm_line;
     ";
 
-    let rows = client.query(query, &[]).await?;
+    let rows = client.query(query, &[]).await.map_err(DbError::Query)?;
     let mut graph: Graph = BTreeMap::new();
 
     for row in rows {
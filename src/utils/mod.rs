@@ -1,3 +1,4 @@
 pub mod load_graph;
+pub mod result_cache;
 
 pub use load_graph::load_graph_from_db;
\ No newline at end of file
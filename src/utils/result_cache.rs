@@ -0,0 +1,215 @@
+use geo_types::Point;
+use log::warn;
+use lru::LruCache;
+use prometheus::IntCounter;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+use crate::metrics;
+
+/// Tamaño y vigencia de un caché de resultados. Con `capacity = 0` el caché no guarda nada.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheConfig {
+    pub capacity: usize,
+    pub ttl: Duration,
+    /// Tamaño de la grilla en grados a la que se ajustan las coordenadas de la llave
+    pub grid: f64,
+}
+
+/// Tamaño de celda por defecto para `snap`, unos 50 m.
+const DEFAULT_GRID_DEGREES: f64 = 0.0005;
+
+/// Interpreta `CACHE_GRID_DEGREES`; un valor no finito o no positivo dejaría
+/// `snap` sin sentido, así que se usa el de por defecto.
+fn parse_grid(value: Option<&str>) -> f64 {
+    let Some(value) = value else {
+        return DEFAULT_GRID_DEGREES;
+    };
+    match value.trim().parse::<f64>() {
+        Ok(grid) if grid.is_finite() && grid > 0.0 => grid,
+        _ => {
+            warn!(
+                "Invalid CACHE_GRID_DEGREES {:?}, using default {}",
+                value, DEFAULT_GRID_DEGREES
+            );
+            DEFAULT_GRID_DEGREES
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Lee `<PREFIX>_CACHE_SIZE`, `<PREFIX>_CACHE_TTL_SECS` y `CACHE_GRID_DEGREES`.
+    pub fn from_env(prefix: &str, capacity: usize, ttl_secs: u64) -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+        }
+
+        Self {
+            capacity: var(&format!("{}_CACHE_SIZE", prefix), capacity),
+            ttl: Duration::from_secs(var(&format!("{}_CACHE_TTL_SECS", prefix), ttl_secs)),
+            grid: parse_grid(env::var("CACHE_GRID_DEGREES").ok().as_deref()),
+        }
+    }
+
+    /// Ajusta un punto a la grilla para que consultas casi idénticas compartan llave.
+    pub fn snap(&self, point: Point<f64>) -> (i64, i64) {
+        (
+            (point.x() / self.grid).round() as i64,
+            (point.y() / self.grid).round() as i64,
+        )
+    }
+}
+
+/// Contadores de un caché.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub capacity: usize,
+    pub ttl_secs: u64,
+    /// Veces que se vació por recarga de datos
+    pub invalidations: u64,
+}
+
+/// Caché LRU con vencimiento por entrada y contadores de aciertos.
+pub struct ResultCache<K: Hash + Eq, V: Clone> {
+    entries: Option<Mutex<LruCache<K, (Instant, V)>>>,
+    config: CacheConfig,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+    /// Contadores de Prometheus (aciertos, fallos), si el caché tiene nombre
+    counters: Option<(IntCounter, IntCounter)>,
+}
+
+impl<K: Hash + Eq, V: Clone> ResultCache<K, V> {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            entries: NonZeroUsize::new(config.capacity).map(|capacity| Mutex::new(LruCache::new(capacity))),
+            config,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+            counters: None,
+        }
+    }
+
+    /// Cuenta aciertos y fallos en `cache_hits_total{cache="<name>"}` y `cache_misses_total`.
+    pub fn named(mut self, name: &str) -> Self {
+        self.counters = Some(metrics::cache_counters(name));
+        self
+    }
+
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let entries = self.entries.as_ref()?;
+        let mut entries = entries.lock().unwrap();
+
+        let value = match entries.get(key) {
+            Some((stored, value)) if stored.elapsed() < self.config.ttl => Some(value.clone()),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        };
+        let counter = if value.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        if let Some((hits, misses)) = &self.counters {
+            if value.is_some() { hits.inc() } else { misses.inc() }
+        }
+        value
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        if let Some(entries) = &self.entries {
+            entries.lock().unwrap().put(key, (Instant::now(), value));
+        }
+    }
+
+    /// Vacía el caché, por ejemplo cuando se recargan los datos.
+    pub fn invalidate(&self) {
+        if let Some(entries) = &self.entries {
+            entries.lock().unwrap().clear();
+        }
+        self.invalidations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.as_ref().map_or(0, |entries| entries.lock().unwrap().len()),
+            capacity: self.config.capacity,
+            ttl_secs: self.config.ttl.as_secs(),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(capacity: usize, ttl: Duration) -> CacheConfig {
+        CacheConfig { capacity, ttl, grid: 0.001 }
+    }
+
+    #[test]
+    fn test_parse_grid() {
+        assert_eq!(parse_grid(None), DEFAULT_GRID_DEGREES);
+        assert_eq!(parse_grid(Some("0.001")), 0.001);
+        for invalid in ["0", "-0.001", "NaN", "inf", "abc"] {
+            assert_eq!(parse_grid(Some(invalid)), DEFAULT_GRID_DEGREES);
+        }
+    }
+
+    #[test]
+    fn test_hits_misses_and_expiry() {
+        let cache = ResultCache::new(config(2, Duration::from_secs(60)));
+        assert_eq!(cache.get(&1), None);
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        cache.insert(3, "c"); // expulsa a 1
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&3), Some("c"));
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+
+        cache.invalidate();
+        assert_eq!(cache.get(&3), None);
+        assert_eq!(cache.stats().invalidations, 1);
+
+        let expired = ResultCache::new(config(2, Duration::ZERO));
+        expired.insert(1, "a");
+        assert_eq!(expired.get(&1), None);
+
+        let disabled = ResultCache::new(config(0, Duration::from_secs(60)));
+        disabled.insert(1, "a");
+        assert_eq!(disabled.get(&1), None);
+    }
+
+    #[test]
+    fn test_snap_to_grid() {
+        let config = config(1, Duration::ZERO);
+        assert_eq!(
+            config.snap(Point::new(-89.19141, 13.69891)),
+            config.snap(Point::new(-89.19139, 13.69909))
+        );
+        assert_ne!(
+            config.snap(Point::new(-89.1914, 13.6989)),
+            config.snap(Point::new(-89.1934, 13.6989))
+        );
+    }
+}